@@ -0,0 +1,7 @@
+-- Blocks on the main chain, stored so nodes can reload their chain after a restart
+CREATE TABLE blocks (
+    block_index BIGINT PRIMARY KEY,
+    hash TEXT NOT NULL,
+    previous_block_hash TEXT NOT NULL,
+    data TEXT NOT NULL
+);
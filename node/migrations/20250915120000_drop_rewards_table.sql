@@ -0,0 +1,2 @@
+-- Mining rewards are now paid by the coinbase transaction of each block
+DROP TABLE IF EXISTS rewards;
@@ -1,8 +1,34 @@
+use std::path::PathBuf;
 use clap::{Parser};
 use crate::args::node_type::NodeType;
+use crate::constants::{MAX_MEMPOOL_TRANSACTIONS, MEMPOOL_EXPIRY};
 
 #[derive(Parser, Debug, Clone)]
 pub struct Args {
     #[clap(subcommand)]
-    pub node_type: NodeType
-}
\ No newline at end of file
+    pub node_type: NodeType,
+
+    /// JSON chain spec defining the network, the default network is used without one
+    #[clap(long, global = true)]
+    pub chain_spec: Option<PathBuf>,
+
+    /// Do not mine on this node, blocks come from external miners using the mining job API
+    #[clap(long, global = true)]
+    pub no_mining: bool,
+
+    /// Number of threads used to search for a block's nonce
+    #[clap(long, global = true, default_value_t = 1)]
+    pub mining_threads: usize,
+
+    /// Maximum number of pending transactions kept in the mempool
+    #[clap(long, global = true, default_value_t = MAX_MEMPOOL_TRANSACTIONS, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub mempool_max_transactions: usize,
+
+    /// Seconds a transaction may stay pending before it is dropped
+    #[clap(long, global = true, default_value_t = MEMPOOL_EXPIRY, value_parser = clap::value_parser!(i64).range(1..))]
+    pub mempool_expiry: i64,
+
+    /// File the mempool is saved to on shutdown and reloaded from on startup
+    #[clap(long, global = true)]
+    pub mempool_file: Option<PathBuf>
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use anyhow::Result;
+use clap::Parser;
+use MockChain::mining::mining_engine::mine_parallel;
+use MockChain::server::request::mining_submission::MiningSubmissionRequest;
+use MockChain::server::response::block_submission_response::BlockSubmissionResponse;
+use MockChain::server::response::mining_job_response::MiningJobResponse;
+
+/*
+    Standalone miner that works for a FULL node through its mining job API.
+    It fetches a block template, searches for a nonce on several threads
+    and submits the solution, fetching a fresh job every few seconds
+    so that it picks up new transactions and chain tips.
+*/
+
+#[derive(Parser, Debug)]
+struct MinerArgs {
+    /// Address the block rewards are paid to
+    miner_address: String,
+
+    /// HTTP API of the FULL node to mine for
+    #[clap(long, default_value = "http://127.0.0.1:3000")]
+    node_url: String,
+
+    /// Number of threads used to search for a nonce
+    #[clap(long, default_value_t = 1)]
+    threads: usize,
+
+    /// Seconds spent on a job before a fresh one is fetched
+    #[clap(long, default_value_t = 10)]
+    job_refresh: u64
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = MinerArgs::parse();
+    let client = reqwest::Client::new();
+    println!("Mining for {} on {} threads", args.node_url, args.threads);
+
+    loop {
+        let job = match fetch_job(&client, &args).await {
+            Ok(job) => job,
+            Err(e) => {
+                println!("Failed to fetch mining job: {}... Retrying in 5 seconds", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        println!("Received job {} for block {} at difficulty {}", job.job_id, job.index, job.difficulty);
+
+        let cancel_flag = Arc::new(AtomicBool::new(true));
+        let refresh_timer = tokio::spawn({
+            let cancel_flag = cancel_flag.clone();
+            let job_refresh = args.job_refresh;
+            async move {
+                tokio::time::sleep(Duration::from_secs(job_refresh)).await;
+                cancel_flag.store(false, Ordering::Relaxed);
+            }
+        });
+
+        let block = job.to_block();
+        let threads = args.threads;
+        let mined_block = tokio::task::spawn_blocking(move || mine_parallel(block, threads, cancel_flag)).await?;
+        refresh_timer.abort();
+
+        if let Some(block) = mined_block {
+            match submit_nonce(&client, &args, &job.job_id, block.nonce).await {
+                Ok(response) => println!("Submitted block {}: {}", block.index, response.message),
+                Err(e) => println!("Failed to submit block {}: {}", block.index, e)
+            }
+        }
+    }
+}
+
+async fn fetch_job(client: &reqwest::Client, args: &MinerArgs) -> Result<MiningJobResponse> {
+    let url = format!("{}/mining/job/{}", args.node_url, args.miner_address);
+    let job = client.get(url).send().await?.error_for_status()?.json().await?;
+
+    Ok(job)
+}
+
+async fn submit_nonce(client: &reqwest::Client, args: &MinerArgs, job_id: &str, nonce: u64) -> Result<BlockSubmissionResponse> {
+    let url = format!("{}/mining/submit", args.node_url);
+    let request = MiningSubmissionRequest {
+        job_id: job_id.to_string(),
+        nonce
+    };
+
+    // Rejected blocks are reported in the response body
+    Ok(client.post(url).json(&request).send().await?.json().await?)
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use crate::chain::block::Block;
+use crate::chain::transaction::Transaction;
+use crate::chain::wallet::Wallet;
+
+/*
+    Account balances derived by replaying the blocks of the main chain.
+    A block's coinbase is only credited once it has matured, when the block
+    MINING_REWARD_DELAY blocks later is applied.
+    Genesis allocations are the exception and are credited immediately.
+    The changes made by each block are recorded so the block can be
+    reverted when the chain is reorganised, until the block is final.
+    Each account also has a nonce counting the transactions it has sent,
+    a transaction is only applied if it carries the sender's next nonce.
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+enum BalanceChange {
+    Credit(String, u64),
+    Debit(String, u64),
+    Nonce(String)
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct AccountState {
+    pub balances: HashMap<String, u64>,
+    pub nonces: HashMap<String, u64>,
+    // Changes made by each block, keyed by block hash, with the block's index
+    block_changes: HashMap<String, (u64, Vec<BalanceChange>)>
+}
+
+impl AccountState {
+    pub fn new() -> Self {
+        Self {
+            balances: HashMap::new(),
+            nonces: HashMap::new(),
+            block_changes: HashMap::new()
+        }
+    }
+
+    pub fn get_balance(&self, address: &str) -> u64 {
+        self.balances.get(address).copied().unwrap_or(0)
+    }
+
+    // Returns the nonce the next transaction sent from this address must carry
+    pub fn get_nonce(&self, address: &str) -> u64 {
+        self.nonces.get(address).copied().unwrap_or(0)
+    }
+
+    pub fn apply_block(&mut self, block: &Block, matured_coinbase: Option<&Transaction>) -> Result<()> {
+        let mut changes: Vec<BalanceChange> = vec![];
+
+        if let Some(coinbase) = matured_coinbase {
+            self.credit(&coinbase.recipient, coinbase.amount);
+            changes.push(BalanceChange::Credit(coinbase.recipient.clone(), coinbase.amount));
+        }
+
+        if block.index == 0 {
+            for allocation in block.transactions.iter().filter(|transaction| transaction.is_coinbase()) {
+                self.credit(&allocation.recipient, allocation.amount);
+                changes.push(BalanceChange::Credit(allocation.recipient.clone(), allocation.amount));
+            }
+        }
+
+        for transaction in block.transactions.iter().filter(|transaction| !transaction.is_coinbase()) {
+            let sender_address = Wallet::derive_address_hash_from_string(&transaction.sender);
+            if let Err(e) = self.apply_transaction(transaction, &sender_address, &mut changes) {
+                self.revert_changes(&changes);
+                return Err(e);
+            }
+        }
+
+        self.block_changes.insert(block.hash.clone(), (block.index, changes));
+        Ok(())
+    }
+
+    pub fn revert_block(&mut self, block: &Block) {
+        if let Some((_, changes)) = self.block_changes.get(&block.hash).cloned() {
+            self.revert_changes(&changes);
+        }
+    }
+
+    // Drops the changes of blocks below the given index, those blocks can no longer be reverted
+    pub fn prune_block_changes(&mut self, final_index: u64) {
+        self.block_changes.retain(|_, (index, _)| *index >= final_index);
+    }
+
+    // Applies a transaction that is not part of a block yet, used to simulate pending transactions.
+    // The sender address is passed in because the mempool has already derived it.
+    pub fn apply_pending_transaction(&mut self, transaction: &Transaction, sender_address: &str) -> Result<()> {
+        let mut changes: Vec<BalanceChange> = vec![];
+        self.apply_transaction(transaction, sender_address, &mut changes)
+    }
+
+    pub fn get_changed_addresses(&self, block_hash: &str) -> Vec<String> {
+        match self.block_changes.get(block_hash) {
+            Some((_, changes)) => changes.iter().filter_map(|change| match change {
+                BalanceChange::Credit(address, _) => Some(address.clone()),
+                BalanceChange::Debit(address, _) => Some(address.clone()),
+                BalanceChange::Nonce(_) => None
+            }).collect(),
+            None => vec![]
+        }
+    }
+
+    fn apply_transaction(&mut self, transaction: &Transaction, sender_address: &str, changes: &mut Vec<BalanceChange>) -> Result<()> {
+        let expected_nonce = self.get_nonce(sender_address);
+        if transaction.nonce != expected_nonce {
+            return Err(anyhow!("Invalid nonce {} for {}, expected {}", transaction.nonce, sender_address, expected_nonce));
+        }
+
+        let total = transaction.amount.checked_add(transaction.fee)
+            .ok_or(anyhow!("Transaction amount overflows"))?;
+        if self.get_balance(sender_address) < total {
+            return Err(anyhow!("Insufficient funds for {}", sender_address));
+        }
+
+        self.nonces.insert(sender_address.to_string(), expected_nonce + 1);
+        changes.push(BalanceChange::Nonce(sender_address.to_string()));
+
+        // The fee is paid to the miner through the block's coinbase
+        self.debit(sender_address, total);
+        changes.push(BalanceChange::Debit(sender_address.to_string(), total));
+
+        self.credit(&transaction.recipient, transaction.amount);
+        changes.push(BalanceChange::Credit(transaction.recipient.clone(), transaction.amount));
+
+        Ok(())
+    }
+
+    fn revert_changes(&mut self, changes: &[BalanceChange]) {
+        for change in changes.iter().rev() {
+            match change {
+                BalanceChange::Credit(address, amount) => self.debit(address, *amount),
+                BalanceChange::Debit(address, amount) => self.credit(address, *amount),
+                BalanceChange::Nonce(address) => self.decrement_nonce(address)
+            }
+        }
+    }
+
+    fn credit(&mut self, address: &str, amount: u64) {
+        *self.balances.entry(address.to_string()).or_insert(0) += amount;
+    }
+
+    fn decrement_nonce(&mut self, address: &str) {
+        let nonce = self.get_nonce(address).saturating_sub(1);
+        if nonce == 0 {
+            self.nonces.remove(address);
+        } else {
+            self.nonces.insert(address.to_string(), nonce);
+        }
+    }
+
+    fn debit(&mut self, address: &str, amount: u64) {
+        let balance = self.get_balance(address).saturating_sub(amount);
+        if balance == 0 {
+            self.balances.remove(address);
+        } else {
+            self.balances.insert(address.to_string(), balance);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MINING_REWARD_AMOUNT;
+
+    fn create_transaction(sender: &Wallet, recipient: &str, amount: u64, nonce: u64) -> Transaction {
+        Transaction {
+            sender: sender.get_public_key(),
+            recipient: recipient.to_string(),
+            amount,
+            fee: 0,
+            timestamp: 0,
+            nonce,
+            id: String::new(),
+            signature: None
+        }
+    }
+
+    fn create_block(hash: &str, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block::new(1, "previousBlockHash".to_string(), transactions, "minerAddress".to_string());
+        block.hash = hash.to_string();
+        block
+    }
+
+    #[test]
+    fn test_apply_and_revert_block() {
+        let sender = Wallet::new();
+        let mut state = AccountState::new();
+        state.apply_block(&create_block("a", vec![]), Some(&Transaction::new_coinbase(sender.address.clone(), MINING_REWARD_AMOUNT, 0))).unwrap();
+
+        let block = create_block("b", vec![create_transaction(&sender, "recipient", 20, 0)]);
+        state.apply_block(&block, None).unwrap();
+
+        assert_eq!(state.get_balance(&sender.address), MINING_REWARD_AMOUNT - 20);
+        assert_eq!(state.get_balance("recipient"), 20);
+
+        state.revert_block(&block);
+
+        assert_eq!(state.get_balance(&sender.address), MINING_REWARD_AMOUNT);
+        assert_eq!(state.get_balance("recipient"), 0);
+        assert_eq!(state.get_nonce(&sender.address), 0);
+    }
+
+    #[test]
+    fn test_final_block_changes_are_pruned() {
+        let sender = Wallet::new();
+        let mut state = AccountState::new();
+        let funding_block = create_block("a", vec![]);
+        state.apply_block(&funding_block, Some(&Transaction::new_coinbase(sender.address.clone(), MINING_REWARD_AMOUNT, 0))).unwrap();
+        let mut block = create_block("b", vec![create_transaction(&sender, "recipient", 20, 0)]);
+        block.index = 2;
+        state.apply_block(&block, None).unwrap();
+
+        state.prune_block_changes(2);
+
+        assert!(state.get_changed_addresses(&funding_block.hash).is_empty());
+        assert_eq!(state.get_changed_addresses(&block.hash), vec![sender.address.clone(), "recipient".to_string()]);
+        state.revert_block(&block);
+        assert_eq!(state.get_balance(&sender.address), MINING_REWARD_AMOUNT);
+    }
+
+    #[test]
+    fn test_apply_block_insufficient_funds() {
+        let sender = Wallet::new();
+        let mut state = AccountState::new();
+        state.apply_block(&create_block("a", vec![]), Some(&Transaction::new_coinbase(sender.address.clone(), MINING_REWARD_AMOUNT, 0))).unwrap();
+        let expected_state = state.clone();
+
+        let block = create_block("b", vec![
+            create_transaction(&sender, "recipient", MINING_REWARD_AMOUNT, 0),
+            create_transaction(&sender, "recipient", 1, 1)
+        ]);
+
+        assert!(state.apply_block(&block, None).is_err());
+        assert_eq!(state, expected_state);
+    }
+
+    #[test]
+    fn test_replayed_transaction_is_rejected() {
+        let sender = Wallet::new();
+        let mut state = AccountState::new();
+        state.apply_block(&create_block("a", vec![]), Some(&Transaction::new_coinbase(sender.address.clone(), MINING_REWARD_AMOUNT, 0))).unwrap();
+
+        let transaction = create_transaction(&sender, "recipient", 10, 0);
+        state.apply_block(&create_block("b", vec![transaction.clone()]), None).unwrap();
+
+        assert_eq!(state.get_nonce(&sender.address), 1);
+        assert!(state.apply_block(&create_block("c", vec![transaction]), None).is_err());
+        assert_eq!(state.get_balance("recipient"), 10);
+    }
+
+    #[test]
+    fn test_coinbase_is_not_credited_until_matured() {
+        let mut state = AccountState::new();
+        let coinbase = Transaction::new_coinbase("miner".to_string(), MINING_REWARD_AMOUNT, 1);
+        let mut block = create_block("a", vec![coinbase.clone()]);
+        block.index = 1;
+
+        state.apply_block(&block, None).unwrap();
+        assert_eq!(state.get_balance("miner"), 0);
+
+        state.apply_block(&create_block("b", vec![]), Some(&coinbase)).unwrap();
+        assert_eq!(state.get_balance("miner"), MINING_REWARD_AMOUNT);
+    }
+}
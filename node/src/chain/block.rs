@@ -2,25 +2,63 @@ use std::fmt::Display;
 use chrono::Utc;
 use hex::encode;
 use serde::{Serialize, Deserialize};
-use serde_json::to_string;
 use sha2::{Digest, Sha256};
-use crate::constants::BLOCKCHAIN_DIFFICULTY;
+use crate::chain::difficulty::{get_target, meets_target};
+use crate::constants::{BLOCKCHAIN_DIFFICULTY, MINING_BATCH_SIZE};
+use crate::chain::merkle::merkle_root;
 use crate::chain::transaction::Transaction;
+use crate::chain::wallet::Wallet;
 
 /*
     Each block in the blockchain is a digital container
     that permanently stores transaction data for the network.
 */
 
-#[derive(Serialize, Deserialize)]
-struct HashlessBlock {
-    index: u64,
-    timestamp: i64,
-    transactions: Vec<Transaction>,
-    miner_address: String,
-    previous_block_hash: String,
-    nonce: u64,
-    difficulty: usize,
+// Fixed-size part of a block that is hashed while mining
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub timestamp: i64,
+    pub previous_block_hash: String,
+    pub merkle_root: String,
+    pub miner_address: String,
+    pub nonce: u64,
+    pub difficulty: usize
+}
+
+impl BlockHeader {
+    pub const SIZE: usize = 8 + 8 + 32 + 32 + 32 + 4 + 8;
+    pub const NONCE_OFFSET: usize = Self::SIZE - 8;
+
+    // Big-endian fixed layout: index, timestamp, previous hash, Merkle root, miner address, difficulty, nonce.
+    // The nonce comes last so miners only rewrite the final bytes between attempts.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.index.to_be_bytes());
+        bytes[8..16].copy_from_slice(&self.timestamp.to_be_bytes());
+        bytes[16..48].copy_from_slice(&to_header_field(&self.previous_block_hash));
+        bytes[48..80].copy_from_slice(&to_header_field(&self.merkle_root));
+        bytes[80..112].copy_from_slice(&to_header_field(&self.miner_address));
+        bytes[112..116].copy_from_slice(&(self.difficulty as u32).to_be_bytes());
+        bytes[Self::NONCE_OFFSET..].copy_from_slice(&self.nonce.to_be_bytes());
+
+        bytes
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256::digest(self.to_bytes()).into()
+    }
+}
+
+// Hashes are stored as 32 bytes, any other value (like the genesis parent or an address) through its SHA-256
+fn to_header_field(value: &str) -> [u8; 32] {
+    let mut field = [0u8; 32];
+    match hex::decode(value) {
+        Ok(bytes) if bytes.len() == 32 => field.copy_from_slice(&bytes),
+        _ => field = Sha256::digest(value.as_bytes()).into()
+    }
+
+    field
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -29,6 +67,7 @@ pub struct Block {
     pub timestamp: i64,
     pub transactions: Vec<Transaction>,
     pub previous_block_hash: String,
+    pub merkle_root: String,
     pub miner_address: String,
     pub nonce: u64,
     pub difficulty: usize,
@@ -40,6 +79,7 @@ impl Block {
         Self {
             index,
             timestamp: Utc::now().timestamp(),
+            merkle_root: merkle_root(&transactions),
             transactions,
             previous_block_hash,
             miner_address,
@@ -50,40 +90,85 @@ impl Block {
     }
 
     pub fn mine(&mut self) -> bool {
-        let hash: String = self.create_hash();
+        self.mine_with_step(1, MINING_BATCH_SIZE)
+    }
 
-        if hash.starts_with(&"0".repeat(self.difficulty)) {
-            self.hash = hash;
-            return true;
-        }
+    // Tries up to attempts nonces from the current one, advancing by step so parallel workers can share the nonce space.
+    // The header is serialized once and only its nonce bytes are rewritten for each attempt.
+    pub fn mine_with_step(&mut self, step: u64, attempts: u64) -> bool {
+        let mut header = self.get_header().to_bytes();
+        let target = get_target(self.difficulty);
+
+        for _ in 0..attempts {
+            header[BlockHeader::NONCE_OFFSET..].copy_from_slice(&self.nonce.to_be_bytes());
+            let hash: [u8; 32] = Sha256::digest(header).into();
 
-        self.nonce += 1;
+            if meets_target(&hash, &target) {
+                self.hash = encode(hash);
+                return true;
+            }
+
+            self.nonce = self.nonce.wrapping_add(step);
+        }
 
         false
     }
 
-    pub fn create_hash(&self) -> String {
-        let hashless_block = HashlessBlock {
+    pub fn get_header(&self) -> BlockHeader {
+        BlockHeader {
             index: self.index,
             timestamp: self.timestamp,
-            transactions: self.transactions.clone(),
             previous_block_hash: self.previous_block_hash.clone(),
+            merkle_root: self.merkle_root.clone(),
             miner_address: self.miner_address.clone(),
             nonce: self.nonce,
             difficulty: self.difficulty
-        };
+        }
+    }
+
+    pub fn create_hash(&self) -> String {
+        encode(self.get_header().hash())
+    }
+
+    // The stored hash must be the header hash and fall below the target for the block's difficulty
+    pub fn has_valid_proof_of_work(&self) -> bool {
+        let hash = self.get_header().hash();
+
+        self.hash == encode(hash) && meets_target(&hash, &get_target(self.difficulty))
+    }
+
+    // The header only commits to the transactions through the Merkle root
+    pub fn has_valid_merkle_root(&self) -> bool {
+        self.merkle_root == merkle_root(&self.transactions)
+    }
 
-        let serialized: String = to_string(&hashless_block).expect("Failed to serialize block");
+    // Every transaction must be signed by its sender
+    pub fn has_valid_signatures(&self) -> bool {
+        self.find_invalid_signature().is_none()
+    }
 
-        Self::calculate_hash(serialized)
+    pub fn find_invalid_signature(&self) -> Option<&Transaction> {
+        self.transactions.iter().filter(|transaction| !transaction.is_coinbase()).find(|transaction| {
+            match Wallet::try_load_from_public_key(&transaction.sender) {
+                Some(wallet) => !wallet.verify_signature(transaction),
+                None => true
+            }
+        })
     }
 
-    pub fn calculate_hash(serialized_data: String) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(serialized_data.as_bytes());
-        let result = hasher.finalize();
+    pub fn get_coinbase(&self) -> Option<&Transaction> {
+        self.transactions.first().filter(|transaction| transaction.is_coinbase())
+    }
 
-        encode(result)
+    // Sum of the fees paid by every transaction other than the coinbase
+    pub fn get_total_fees(&self) -> Option<u64> {
+        self.transactions.iter()
+            .filter(|transaction| !transaction.is_coinbase())
+            .try_fold(0u64, |total, transaction| total.checked_add(transaction.fee))
+    }
+
+    pub fn get_size(&self) -> usize {
+        serde_json::to_vec(self).expect("Failed to serialize block").len()
     }
 
     pub fn equals(&self, other: &Block) -> bool {
@@ -91,6 +176,7 @@ impl Block {
         self.timestamp == other.timestamp &&
         self.transactions == other.transactions &&
         self.previous_block_hash == other.previous_block_hash &&
+        self.merkle_root == other.merkle_root &&
         self.miner_address == other.miner_address &&
         self.nonce == other.nonce &&
         self.difficulty == other.difficulty &&
@@ -108,12 +194,29 @@ impl Display for Block {
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    fn create_signed_transaction(wallet: &Wallet) -> Transaction {
+        let mut transaction = Transaction {
+            sender: wallet.get_public_key(),
+            recipient: "recipient".to_string(),
+            amount: 10,
+            fee: 0,
+            timestamp: 0,
+            nonce: 0,
+            id: String::new(),
+            signature: None
+        };
+        transaction.id = transaction.create_id();
+        wallet.create_signature(&mut transaction);
+
+        transaction
+    }
+
     #[test]
     fn test_constructor() {
         let new_block = Block::new(0, "previousBlockHash".to_string(), vec![], "minerAddress".to_string());
 
-        assert_eq!(new_block.difficulty, 0);
+        assert_eq!(new_block.difficulty, BLOCKCHAIN_DIFFICULTY);
         assert!(new_block.transactions.is_empty());
         assert_eq!(new_block.index, 0);
         assert_eq!(new_block.previous_block_hash, "previousBlockHash".to_string());
@@ -122,14 +225,70 @@ mod tests {
         assert_eq!(new_block.nonce, 0);
     }
 
+    #[test]
+    fn test_header_layout() {
+        let mut new_block = Block::new(7, "previousBlockHash".to_string(), vec![], "minerAddress".to_string());
+        let bytes = new_block.get_header().to_bytes();
+        new_block.nonce = 1;
+        let next_bytes = new_block.get_header().to_bytes();
+
+        assert_eq!(bytes[0..8], 7u64.to_be_bytes());
+        assert_eq!(bytes[48..80], hex::decode(&new_block.merkle_root).unwrap()[..]);
+        assert_eq!(bytes[..116], next_bytes[..116]);
+        assert_eq!(next_bytes[116..], 1u64.to_be_bytes());
+    }
+
     #[test]
     fn test_mine() {
         let mut new_block = Block::new(0, "previousBlockHash".to_string(), vec![], "minerAddress".to_string());
 
-        new_block.mine();
+        while !new_block.mine() {}
 
-        assert_eq!(new_block.hash, new_block.create_hash());
-        assert!(new_block.hash.starts_with(&"0".repeat(2)));
+        assert!(new_block.has_valid_proof_of_work());
         assert!(new_block.nonce > 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_mine_with_step_skips_nonces() {
+        let mut new_block = Block::new(0, "previousBlockHash".to_string(), vec![], "minerAddress".to_string());
+        new_block.difficulty = 255;
+
+        assert!(!new_block.mine_with_step(3, 10));
+        assert_eq!(new_block.nonce, 30);
+    }
+
+    #[test]
+    fn test_signed_transactions_survive_serialization() {
+        let transaction = create_signed_transaction(&Wallet::new());
+        let block = Block::new(1, "previousBlockHash".to_string(), vec![transaction], "minerAddress".to_string());
+
+        let serialized = serde_json::to_string(&block).unwrap();
+        let deserialized: Block = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(deserialized, block);
+        assert!(deserialized.has_valid_signatures());
+    }
+
+    #[test]
+    fn test_tampered_transaction_fails_verification() {
+        let mut transaction = create_signed_transaction(&Wallet::new());
+        transaction.amount = 1000;
+        let block = Block::new(1, "previousBlockHash".to_string(), vec![transaction], "minerAddress".to_string());
+
+        assert!(!block.has_valid_signatures());
+    }
+
+    #[test]
+    fn test_block_hash_commits_to_signatures() {
+        let wallet = Wallet::new();
+        let transaction = create_signed_transaction(&wallet);
+        let mut block = Block::new(1, "previousBlockHash".to_string(), vec![transaction], "minerAddress".to_string());
+        let hash = block.create_hash();
+
+        block.transactions[0].signature = None;
+        block.merkle_root = merkle_root(&block.transactions);
+
+        assert_ne!(block.create_hash(), hash);
+        assert!(!block.has_valid_signatures());
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use chrono::Utc;
+use crate::chain::block::Block;
+use crate::chain::difficulty::block_work;
+use crate::constants::{MAX_ORPHAN_BLOCKS, ORPHAN_EXPIRY};
+
+/*
+    Index of every known block, including blocks on side branches.
+    Each block is linked to its parent through previous_block_hash and
+    stores the cumulative proof-of-work of the branch it completes.
+    Blocks whose parent is not known yet are held as orphans,
+    grouped by the hash of the parent they are waiting for.
+    When the orphan pool is full the oldest orphan is evicted,
+    and orphans whose parent has not arrived within the expiry are dropped.
+    Blocks that failed to apply to the account state are marked invalid
+    so that no branch containing them is chosen again.
+*/
+
+#[derive(Clone, Default)]
+pub struct BlockTree {
+    pub blocks: HashMap<String, Block>,
+    pub chain_work: HashMap<String, u128>,
+    pub orphans: HashMap<String, Vec<Block>>,
+    // Orphan hashes with the time they were received, oldest first
+    pub orphan_arrivals: VecDeque<(String, i64)>,
+    pub invalid: HashSet<String>
+}
+
+impl BlockTree {
+    pub fn new() -> Self {
+        Self {
+            blocks: HashMap::new(),
+            chain_work: HashMap::new(),
+            orphans: HashMap::new(),
+            orphan_arrivals: VecDeque::new(),
+            invalid: HashSet::new()
+        }
+    }
+
+    pub fn insert(&mut self, block: Block) {
+        let parent_work = self.get_chain_work(&block.previous_block_hash);
+        let work = parent_work.saturating_add(block_work(block.difficulty));
+
+        self.chain_work.insert(block.hash.clone(), work);
+        self.blocks.insert(block.hash.clone(), block);
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&Block> {
+        self.blocks.get(hash)
+    }
+
+    pub fn get_chain_work(&self, hash: &str) -> u128 {
+        self.chain_work.get(hash).copied().unwrap_or(0)
+    }
+
+    // Returns the branch ending at the given block, ordered from the root
+    pub fn get_branch(&self, tip_hash: &str) -> Vec<Block> {
+        let mut branch: Vec<Block> = vec![];
+        let mut current = self.blocks.get(tip_hash);
+
+        while let Some(block) = current {
+            branch.push(block.clone());
+            if block.index == 0 {
+                break;
+            }
+            current = self.blocks.get(&block.previous_block_hash);
+        }
+
+        branch.reverse();
+        branch
+    }
+
+    pub fn mark_invalid(&mut self, hash: &str) {
+        self.invalid.insert(hash.to_string());
+    }
+
+    pub fn is_invalid(&self, hash: &str) -> bool {
+        self.invalid.contains(hash)
+    }
+
+    pub fn add_orphan(&mut self, block: Block) -> bool {
+        self.add_orphan_at(block, Utc::now().timestamp())
+    }
+
+    pub fn add_orphan_at(&mut self, block: Block, received_at: i64) -> bool {
+        if self.contains_orphan(&block.hash) {
+            return false;
+        }
+
+        self.remove_expired_orphans(received_at);
+        if self.get_orphan_count() >= MAX_ORPHAN_BLOCKS
+            && let Some((oldest_hash, _)) = self.orphan_arrivals.pop_front() {
+            println!("Orphan pool is full... Evicting block {}", oldest_hash);
+            self.remove_orphan(&oldest_hash);
+        }
+
+        self.orphan_arrivals.push_back((block.hash.clone(), received_at));
+        self.orphans.entry(block.previous_block_hash.clone()).or_default().push(block);
+        true
+    }
+
+    pub fn take_orphans(&mut self, parent_hash: &str) -> Vec<Block> {
+        let orphans = self.orphans.remove(parent_hash).unwrap_or_default();
+        self.orphan_arrivals.retain(|(hash, _)| !orphans.iter().any(|orphan| orphan.hash == *hash));
+
+        orphans
+    }
+
+    // Drops orphans received ORPHAN_EXPIRY seconds or more before now, returning how many were dropped
+    pub fn remove_expired_orphans(&mut self, now: i64) -> usize {
+        let mut count = 0;
+        while let Some((hash, received_at)) = self.orphan_arrivals.front().cloned() {
+            if now - received_at < ORPHAN_EXPIRY {
+                break;
+            }
+
+            self.orphan_arrivals.pop_front();
+            self.remove_orphan(&hash);
+            count += 1;
+        }
+
+        count
+    }
+
+    fn remove_orphan(&mut self, hash: &str) {
+        for blocks in self.orphans.values_mut() {
+            blocks.retain(|block| block.hash != hash);
+        }
+        self.orphans.retain(|_, blocks| !blocks.is_empty());
+    }
+
+    pub fn contains_orphan(&self, hash: &str) -> bool {
+        self.orphans.values().flatten().any(|block| block.hash == hash)
+    }
+
+    pub fn get_orphan_count(&self) -> usize {
+        self.orphans.values().map(|blocks| blocks.len()).sum()
+    }
+
+    // Returns the hashes of known blocks that orphans are waiting for
+    pub fn get_connectable_orphan_parents(&self) -> Vec<String> {
+        self.orphans.keys()
+            .filter(|parent_hash| self.contains(parent_hash))
+            .cloned()
+            .collect()
+    }
+
+    // Removes every connected block, orphans are kept until their parent arrives
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.chain_work.clear();
+        self.invalid.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_block(index: u64, previous_block_hash: &str, hash: &str, difficulty: usize) -> Block {
+        let mut block = Block::new(index, previous_block_hash.to_string(), vec![], "minerAddress".to_string());
+        block.difficulty = difficulty;
+        block.hash = hash.to_string();
+        block
+    }
+
+    #[test]
+    fn test_chain_work_is_cumulative() {
+        let mut tree = BlockTree::new();
+        tree.insert(create_block(0, "0", "a", 1));
+        tree.insert(create_block(1, "a", "b", 2));
+
+        assert_eq!(tree.get_chain_work("a"), 2);
+        assert_eq!(tree.get_chain_work("b"), 2 + 4);
+    }
+
+    #[test]
+    fn test_get_branch_follows_parent_links() {
+        let mut tree = BlockTree::new();
+        tree.insert(create_block(0, "0", "a", 1));
+        tree.insert(create_block(1, "a", "b", 1));
+        tree.insert(create_block(1, "a", "c", 1));
+        tree.insert(create_block(2, "c", "d", 1));
+
+        let hashes: Vec<String> = tree.get_branch("d").iter().map(|b| b.hash.clone()).collect();
+
+        assert_eq!(hashes, vec!["a", "c", "d"]);
+    }
+
+    #[test]
+    fn test_orphans_grouped_by_parent() {
+        let mut tree = BlockTree::new();
+        assert!(tree.add_orphan(create_block(2, "b", "c", 1)));
+        assert!(tree.add_orphan(create_block(2, "b", "d", 1)));
+        assert!(!tree.add_orphan(create_block(2, "b", "d", 1)));
+
+        assert_eq!(tree.get_orphan_count(), 2);
+        assert!(tree.contains_orphan("c"));
+        assert_eq!(tree.take_orphans("b").len(), 2);
+        assert_eq!(tree.get_orphan_count(), 0);
+    }
+
+    #[test]
+    fn test_full_orphan_pool_evicts_oldest() {
+        let mut tree = BlockTree::new();
+        for index in 0..MAX_ORPHAN_BLOCKS {
+            tree.add_orphan(create_block(1, "unknown", &index.to_string(), 1));
+        }
+
+        assert!(tree.add_orphan(create_block(1, "unknown", "newest", 1)));
+        assert_eq!(tree.get_orphan_count(), MAX_ORPHAN_BLOCKS);
+        assert!(!tree.contains_orphan("0"));
+        assert!(tree.contains_orphan("newest"));
+    }
+
+    #[test]
+    fn test_expired_orphans_are_removed() {
+        let mut tree = BlockTree::new();
+        tree.add_orphan_at(create_block(2, "b", "c", 1), 1000);
+        tree.add_orphan_at(create_block(3, "c", "d", 1), 1000 + ORPHAN_EXPIRY - 1);
+
+        assert_eq!(tree.remove_expired_orphans(1000 + ORPHAN_EXPIRY), 1);
+        assert!(!tree.contains_orphan("c"));
+        assert!(tree.contains_orphan("d"));
+        assert!(!tree.orphans.contains_key("b"));
+    }
+
+    #[test]
+    fn test_get_branch_unknown_tip() {
+        let tree = BlockTree::new();
+
+        assert!(tree.get_branch("unknown").is_empty());
+    }
+}
@@ -1,6 +1,63 @@
+use std::fmt::Display;
+
 #[derive(PartialEq, Debug)]
 pub enum BlockValidationType {
     Valid,
-    Invalid,
-    Fork
-}
\ No newline at end of file
+    Invalid(InvalidBlockReason),
+    Fork,
+    SideChain,
+    Reorg
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum InvalidBlockReason {
+    UnexpectedGenesis,
+    KnownBlock,
+    InvalidParent,
+    InvalidIndex,
+    TooManyTransactions,
+    BlockTooLarge,
+    InvalidProofOfWork,
+    InvalidDifficulty { expected: usize, found: usize },
+    TimestampTooEarly,
+    TimestampTooFarInFuture,
+    InvalidMerkleRoot,
+    InvalidCoinbase(String),
+    DuplicateTransaction(String),
+    InvalidSignature(String),
+    InvalidState(String)
+}
+
+impl Display for InvalidBlockReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidBlockReason::UnexpectedGenesis => write!(f, "block is not the genesis block from the chain spec"),
+            InvalidBlockReason::KnownBlock => write!(f, "block is already known"),
+            InvalidBlockReason::InvalidParent => write!(f, "block extends an invalid block"),
+            InvalidBlockReason::InvalidIndex => write!(f, "index does not follow the parent block"),
+            InvalidBlockReason::TooManyTransactions => write!(f, "too many transactions"),
+            InvalidBlockReason::BlockTooLarge => write!(f, "block is too large"),
+            InvalidBlockReason::InvalidProofOfWork => write!(f, "hash does not meet the proof-of-work"),
+            InvalidBlockReason::InvalidDifficulty { expected, found } => write!(f, "difficulty {} does not match expected {}", found, expected),
+            InvalidBlockReason::TimestampTooEarly => write!(f, "timestamp is before the median of recent blocks"),
+            InvalidBlockReason::TimestampTooFarInFuture => write!(f, "timestamp is too far in the future"),
+            InvalidBlockReason::InvalidMerkleRoot => write!(f, "Merkle root does not match the transactions"),
+            InvalidBlockReason::InvalidCoinbase(error) => write!(f, "invalid coinbase: {}", error),
+            InvalidBlockReason::DuplicateTransaction(id) => write!(f, "duplicate transaction {}", id),
+            InvalidBlockReason::InvalidSignature(id) => write!(f, "invalid signature on transaction {}", id),
+            InvalidBlockReason::InvalidState(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl Display for BlockValidationType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockValidationType::Valid => write!(f, "block extends the main chain"),
+            BlockValidationType::Invalid(reason) => write!(f, "invalid block: {}", reason),
+            BlockValidationType::Fork => write!(f, "parent block is unknown"),
+            BlockValidationType::SideChain => write!(f, "block stored on a side chain"),
+            BlockValidationType::Reorg => write!(f, "block switched the main chain")
+        }
+    }
+}
@@ -1,83 +1,475 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use crate::chain::account_state::AccountState;
 use crate::chain::block::Block;
-use crate::chain::block_validation_type::BlockValidationType;
-use crate::constants::BLOCKCHAIN_DIFFICULTY;
+use crate::chain::block_tree::BlockTree;
+use crate::chain::block_validation_type::{BlockValidationType, InvalidBlockReason};
+use crate::chain::chain_spec::ChainSpec;
+use crate::chain::chain_update::ChainUpdate;
+use crate::chain::difficulty::next_difficulty;
+use crate::chain::emission::block_subsidy;
+use crate::chain::genesis::build_genesis_block;
+use crate::chain::transaction::Transaction;
+use crate::constants::{FINALITY_DEPTH, MAX_BLOCK_SIZE, MAX_BLOCK_TRANSACTIONS, MAX_FUTURE_BLOCK_TIME, MEDIAN_TIME_BLOCKS};
 
 #[derive(Clone)]
 pub struct Blockchain {
     pub chain: Vec<Block>,
-    pub invalid_blocks: Vec<Block>,
-    pub pending_blocks: Vec<Block>,
+    pub block_tree: BlockTree,
+    pub state: AccountState,
+    pub spec: ChainSpec,
+    genesis: OnceLock<Block>
 }
 
 impl Blockchain {
     pub fn new() -> Self {
+        Self::with_spec(ChainSpec::default())
+    }
+
+    // The genesis block is built from the spec the first time it is needed
+    pub fn with_spec(spec: ChainSpec) -> Self {
         Self {
             chain: vec![],
-            invalid_blocks: vec![],
-            pending_blocks: vec![],
+            block_tree: BlockTree::new(),
+            state: AccountState::new(),
+            spec,
+            genesis: OnceLock::new()
         }
     }
 
+    pub fn with_genesis(spec: ChainSpec, genesis: Block) -> Self {
+        let blockchain = Self::with_spec(spec);
+        blockchain.genesis.get_or_init(|| genesis);
+
+        blockchain
+    }
+
+    pub fn get_genesis_block(&self) -> &Block {
+        self.genesis.get_or_init(|| build_genesis_block(&self.spec).expect("Chain spec does not produce a valid genesis block"))
+    }
+
+    // The whole block is compared because the hash of an untrusted block is unchecked,
+    // and the genesis block's proof-of-work is never validated
+    pub fn is_expected_genesis(&self, block: &Block) -> bool {
+        *block == *self.get_genesis_block()
+    }
+
     pub fn is_valid_new_block(&mut self, new_block: &Block) -> BlockValidationType {
-        if self.chain.len() == 0 {
+        if self.chain.is_empty() {
+            if !self.is_expected_genesis(new_block) {
+                return Self::invalid(new_block, InvalidBlockReason::UnexpectedGenesis);
+            }
+
             return BlockValidationType::Valid;
         }
 
-        let last_block = self.chain.last().unwrap();
+        let parent_block = match self.block_tree.get(&new_block.previous_block_hash) {
+            Some(block) => block.clone(),
+            None => {
+                if let Err(reason) = Self::check_block_without_parent(new_block) {
+                    return Self::invalid(new_block, reason);
+                }
+
+                println!("Found unknown previous block hash");
+                println!("Storing block as an orphan until its parent arrives");
+                self.block_tree.add_orphan(new_block.clone());
+
+                return BlockValidationType::Fork;
+            }
+        };
+
+        if self.block_tree.contains(&new_block.hash) {
+            return Self::invalid(new_block, InvalidBlockReason::KnownBlock);
+        }
+
+        if self.block_tree.is_invalid(&parent_block.hash) {
+            self.block_tree.mark_invalid(&new_block.hash);
+            return Self::invalid(new_block, InvalidBlockReason::InvalidParent);
+        }
 
-        if new_block.previous_block_hash != last_block.hash {
-            println!("Found invalid previous block hash");
-            println!("Storing block in case of forked chain");
-            self.invalid_blocks.push(new_block.clone());
+        let extends_tip = parent_block.hash == self.get_latest_block().hash;
+        let side_branch = if extends_tip { None } else { Some(self.block_tree.get_branch(&parent_block.hash)) };
+        let branch: &[Block] = side_branch.as_deref().unwrap_or(&self.chain);
 
-            return BlockValidationType::Fork;
+        if let Err(reason) = Self::check_block(&self.spec, new_block, &parent_block, branch) {
+            return Self::invalid(new_block, reason);
         }
 
-        if new_block.index == last_block.index + 1 && new_block.hash.starts_with(&"0".repeat(BLOCKCHAIN_DIFFICULTY)) && new_block.hash == new_block.create_hash() {
+        if extends_tip {
             return BlockValidationType::Valid;
         }
 
-        BlockValidationType::Invalid
+        BlockValidationType::SideChain
     }
 
-    pub fn add_pending_block(&mut self, new_block: Block) {
-        self.pending_blocks.push(new_block);
+    // Consensus checks against the branch the block extends, cheapest checks first
+    fn check_block(spec: &ChainSpec, new_block: &Block, parent_block: &Block, branch: &[Block]) -> Result<(), InvalidBlockReason> {
+        if new_block.index != parent_block.index + 1 {
+            return Err(InvalidBlockReason::InvalidIndex);
+        }
+
+        Self::check_block_without_parent(new_block)?;
+
+        let expected_difficulty = next_difficulty(branch, &spec.difficulty);
+        if new_block.difficulty != expected_difficulty {
+            return Err(InvalidBlockReason::InvalidDifficulty { expected: expected_difficulty, found: new_block.difficulty });
+        }
+
+        if new_block.timestamp < Self::get_median_time_past(branch) {
+            return Err(InvalidBlockReason::TimestampTooEarly);
+        }
+
+        if new_block.timestamp > Utc::now().timestamp() + MAX_FUTURE_BLOCK_TIME {
+            return Err(InvalidBlockReason::TimestampTooFarInFuture);
+        }
+
+        let mut transaction_ids: HashSet<&str> = HashSet::new();
+        for transaction in &new_block.transactions {
+            if !transaction_ids.insert(&transaction.id) {
+                return Err(InvalidBlockReason::DuplicateTransaction(transaction.id.clone()));
+            }
+        }
+
+        let on_chain = branch.iter().flat_map(|block| &block.transactions).find(|transaction| transaction_ids.contains(transaction.id.as_str()));
+        if let Some(transaction) = on_chain {
+            return Err(InvalidBlockReason::DuplicateTransaction(transaction.id.clone()));
+        }
+
+        Self::check_coinbase(spec, new_block)?;
+
+        if let Some(transaction) = new_block.find_invalid_signature() {
+            return Err(InvalidBlockReason::InvalidSignature(transaction.id.clone()));
+        }
+
+        Ok(())
+    }
+
+    // Checks that do not depend on the parent, run before a block is stored as an orphan
+    fn check_block_without_parent(new_block: &Block) -> Result<(), InvalidBlockReason> {
+        if new_block.transactions.len() > MAX_BLOCK_TRANSACTIONS {
+            return Err(InvalidBlockReason::TooManyTransactions);
+        }
+
+        if new_block.get_size() > MAX_BLOCK_SIZE {
+            return Err(InvalidBlockReason::BlockTooLarge);
+        }
+
+        if !new_block.has_valid_proof_of_work() {
+            return Err(InvalidBlockReason::InvalidProofOfWork);
+        }
+
+        if !new_block.has_valid_merkle_root() {
+            return Err(InvalidBlockReason::InvalidMerkleRoot);
+        }
+
+        Ok(())
+    }
+
+    // The first transaction must pay exactly the block subsidy plus fees to the miner
+    fn check_coinbase(spec: &ChainSpec, new_block: &Block) -> Result<(), InvalidBlockReason> {
+        let coinbase = new_block.get_coinbase()
+            .ok_or(InvalidBlockReason::InvalidCoinbase("missing coinbase transaction".to_string()))?;
+
+        if coinbase.recipient != new_block.miner_address {
+            return Err(InvalidBlockReason::InvalidCoinbase("coinbase does not pay the miner".to_string()));
+        }
+
+        let expected_amount = new_block.get_total_fees()
+            .and_then(|fees| fees.checked_add(block_subsidy(spec, new_block.index)))
+            .ok_or(InvalidBlockReason::InvalidCoinbase("fees overflow".to_string()))?;
+        if coinbase.amount != expected_amount {
+            return Err(InvalidBlockReason::InvalidCoinbase(format!("coinbase pays {} instead of {}", coinbase.amount, expected_amount)));
+        }
+
+        if coinbase.nonce != new_block.index || coinbase.id != coinbase.create_id() {
+            return Err(InvalidBlockReason::InvalidCoinbase("coinbase is not bound to the block".to_string()));
+        }
+
+        if new_block.transactions.iter().skip(1).any(|transaction| transaction.is_coinbase()) {
+            return Err(InvalidBlockReason::InvalidCoinbase("more than one coinbase transaction".to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn invalid(new_block: &Block, reason: InvalidBlockReason) -> BlockValidationType {
+        println!("Block {} is invalid: {}", new_block.index, reason);
+        BlockValidationType::Invalid(reason)
+    }
+
+    fn get_median_time_past(branch: &[Block]) -> i64 {
+        let recent_blocks = &branch[branch.len().saturating_sub(MEDIAN_TIME_BLOCKS)..];
+        Self::get_median_timestamp(recent_blocks.iter().map(|block| block.timestamp).collect())
+    }
+
+    pub fn get_median_timestamp(mut timestamps: Vec<i64>) -> i64 {
+        if timestamps.is_empty() {
+            return i64::MIN;
+        }
+
+        timestamps.sort();
+        timestamps[timestamps.len() / 2]
+    }
+
+    pub fn add_orphan_block(&mut self, new_block: Block) -> bool {
+        if let Err(reason) = Self::check_block_without_parent(&new_block) {
+            println!("Not storing orphan block {}: {}", new_block.index, reason);
+            return false;
+        }
+
+        self.block_tree.add_orphan(new_block)
     }
 
     pub fn add_block_to_chain(&mut self, new_block: &Block) -> BlockValidationType {
+        let block_validation_type = self.connect_block(new_block);
+        let connected = matches!(
+            block_validation_type,
+            BlockValidationType::Valid | BlockValidationType::SideChain | BlockValidationType::Reorg
+        );
+
+        if connected && self.connect_orphans(&new_block.hash) {
+            return BlockValidationType::Reorg;
+        }
+
+        block_validation_type
+    }
+
+    fn connect_block(&mut self, new_block: &Block) -> BlockValidationType {
         let block_validation_type = self.is_valid_new_block(new_block);
-        if block_validation_type == BlockValidationType::Valid {
-            self.chain.push(new_block.clone());
+        match block_validation_type {
+            BlockValidationType::Valid => {
+                if let Err(e) = self.add_block_without_validation(new_block.clone()) {
+                    return BlockValidationType::Invalid(InvalidBlockReason::InvalidState(e.to_string()));
+                }
+            }
+            BlockValidationType::SideChain => {
+                self.block_tree.insert(new_block.clone());
+                if self.block_tree.get_chain_work(&new_block.hash) > self.get_chain_work() {
+                    println!("Side chain ending at block {} has more work... Reorganising", new_block.index);
+                    if let Err(e) = self.reorganise(&new_block.hash) {
+                        return BlockValidationType::Invalid(InvalidBlockReason::InvalidState(e.to_string()));
+                    }
+
+                    return BlockValidationType::Reorg;
+                }
+            }
+            _ => {}
         }
 
         block_validation_type
     }
 
-    pub fn add_block_without_validation(&mut self, new_block: Block) {
+    // Connects orphans descending from the given block, returns true if the chain was reorganised
+    fn connect_orphans(&mut self, parent_hash: &str) -> bool {
+        let mut reorganised = false;
+        let mut parent_hashes = vec![parent_hash.to_string()];
+
+        while let Some(parent_hash) = parent_hashes.pop() {
+            for orphan in self.block_tree.take_orphans(&parent_hash) {
+                println!("Connecting orphan block {}", orphan.index);
+                match self.connect_block(&orphan) {
+                    BlockValidationType::Reorg => {
+                        reorganised = true;
+                        parent_hashes.push(orphan.hash.clone());
+                    }
+                    BlockValidationType::Valid | BlockValidationType::SideChain => {
+                        parent_hashes.push(orphan.hash.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        reorganised
+    }
+
+    // Skips proof-of-work checks, the block must still apply to the account state
+    pub fn add_block_without_validation(&mut self, new_block: Block) -> Result<()> {
+        let matured_coinbase = Self::get_matured_coinbase(&self.chain, new_block.index, self.spec.rewards.maturity);
+        if let Err(e) = self.state.apply_block(&new_block, matured_coinbase) {
+            println!("Block {} could not be applied: {}", new_block.index, e);
+            return Err(e);
+        }
+
+        self.block_tree.insert(new_block.clone());
         self.chain.push(new_block);
+        self.state.prune_block_changes(self.get_final_index());
+        Ok(())
     }
 
+    // Blocks below this index are final and can no longer be reverted
+    fn get_final_index(&self) -> u64 {
+        self.chain.last().map_or(0, |block| block.index.saturating_sub(FINALITY_DEPTH))
+    }
 
-    pub fn get_latest_block(&self) -> &Block {
-        self.chain.last().unwrap()
+    // Switches the main chain to the branch ending at the given block,
+    // reverting blocks back to the fork point before applying the new branch
+    fn reorganise(&mut self, tip_hash: &str) -> Result<()> {
+        let new_chain = self.block_tree.get_branch(tip_hash);
+        let fork_index = self.chain.iter()
+            .zip(new_chain.iter())
+            .take_while(|(old_block, new_block)| old_block.hash == new_block.hash)
+            .count();
+        if (fork_index as u64) < self.get_final_index() {
+            return Err(anyhow!("Reorganisation would revert final block {}", fork_index));
+        }
+
+        for block in self.chain[fork_index..].iter().rev() {
+            self.state.revert_block(block);
+        }
+
+        for (position, block) in new_chain.iter().enumerate().skip(fork_index) {
+            let matured_coinbase = Self::get_matured_coinbase(&new_chain, block.index, self.spec.rewards.maturity);
+            if let Err(e) = self.state.apply_block(block, matured_coinbase) {
+                println!("Block {} could not be applied: {}... Keeping current chain", block.index, e);
+
+                for applied_block in new_chain[fork_index..position].iter().rev() {
+                    self.state.revert_block(applied_block);
+                }
+                for old_block in &self.chain[fork_index..] {
+                    let matured_coinbase = Self::get_matured_coinbase(&self.chain, old_block.index, self.spec.rewards.maturity);
+                    self.state.apply_block(old_block, matured_coinbase).expect("Failed to restore chain state");
+                }
+                for invalid_block in &new_chain[position..] {
+                    self.block_tree.mark_invalid(&invalid_block.hash);
+                }
+
+                return Err(e);
+            }
+        }
+
+        self.chain = new_chain;
+        self.state.prune_block_changes(self.get_final_index());
+        Ok(())
     }
 
-    pub fn create_genesis_block(&mut self, miner_address: String) -> Block {
-        let mut genesis = Block::new(0, "0".to_string(), Vec::new(), miner_address);
-        loop {
-            if genesis.mine() {
+    // The coinbase of the block maturity blocks back matures when this block is applied.
+    // Genesis allocations are credited with the genesis block itself so they never mature again.
+    fn get_matured_coinbase(branch: &[Block], block_index: u64, maturity: u64) -> Option<&Transaction> {
+        let matured_index = block_index.checked_sub(maturity).filter(|index| *index > 0)?;
+
+        branch.get(matured_index as usize)?.get_coinbase()
+    }
+
+    pub fn load_chain(&mut self, blocks: Vec<Block>) {
+        self.block_tree.clear();
+        self.chain.clear();
+        self.state = AccountState::new();
+
+        // Only the genesis block from the chain spec is trusted, every later block is fully validated
+        for block in blocks {
+            let block_validation_type = if self.chain.is_empty() {
+                if !self.is_expected_genesis(&block) {
+                    println!("Received chain starts with an unexpected genesis block {}", block.hash);
+                    break;
+                }
+
+                match self.add_block_without_validation(block) {
+                    Ok(_) => BlockValidationType::Valid,
+                    Err(e) => BlockValidationType::Invalid(InvalidBlockReason::InvalidState(e.to_string()))
+                }
+            } else {
+                self.connect_block(&block)
+            };
+
+            if block_validation_type != BlockValidationType::Valid {
                 break;
             }
         }
 
+        self.connect_known_orphans();
+    }
+
+    // Connects orphans that were stored while their parent was already known
+    pub fn connect_known_orphans(&mut self) -> bool {
+        let mut reorganised = false;
+        for parent_hash in self.block_tree.get_connectable_orphan_parents() {
+            reorganised |= self.connect_orphans(&parent_hash);
+        }
+
+        reorganised
+    }
+
+    // Compares the current main chain with the chain that ended at previous_tip_hash
+    pub fn get_chain_update(&self, previous_tip_hash: &str) -> ChainUpdate {
+        let mut update = ChainUpdate::default();
+        let mut old_block = self.block_tree.get(previous_tip_hash);
+        let mut new_block = self.chain.last();
+
+        while let Some(block) = new_block {
+            match old_block {
+                Some(old) if old.hash == block.hash => break,
+                Some(old) if old.index >= block.index => {
+                    update.disconnected.push(old.clone());
+                    old_block = self.block_tree.get(&old.previous_block_hash);
+                }
+                _ => {
+                    update.connected.push(block.clone());
+                    new_block = self.block_tree.get(&block.previous_block_hash);
+                }
+            }
+        }
+
+        update.connected.reverse();
+
+        for block in update.disconnected.iter().chain(update.connected.iter()) {
+            for address in self.state.get_changed_addresses(&block.hash) {
+                let balance = self.state.get_balance(&address);
+                update.balances.insert(address, balance);
+            }
+        }
+
+        update
+    }
+
+    // Main chain block containing the transaction with the given id
+    pub fn find_transaction_block(&self, transaction_id: &str) -> Option<&Block> {
+        self.chain.iter().find(|block| block.transactions.iter().any(|transaction| transaction.id == transaction_id))
+    }
+
+    pub fn get_tip_hash(&self) -> String {
+        self.chain.last().map(|block| block.hash.clone()).unwrap_or_default()
+    }
+
+    pub fn get_latest_block(&self) -> &Block {
+        self.chain.last().unwrap()
+    }
+
+    pub fn get_chain_work(&self) -> u128 {
+        match self.chain.last() {
+            Some(block) => self.block_tree.get_chain_work(&block.hash),
+            None => 0
+        }
+    }
+
+    pub fn get_next_difficulty(&self) -> usize {
+        next_difficulty(&self.chain, &self.spec.difficulty)
+    }
+
+    pub fn create_genesis_block(&mut self) -> Block {
+        let genesis = self.get_genesis_block().clone();
         println!("Genesis block: {}", genesis);
-        self.chain.push(genesis.clone());
+        self.add_block_without_validation(genesis.clone()).expect("Failed to add genesis block");
 
         genesis
     }
 
-    pub fn load_starting_block(&mut self, starting_block: Block) {
-        self.chain.push(starting_block);
+    // Returns false without changing the chain unless the chain is empty and the block applies
+    pub fn load_starting_block(&mut self, starting_block: Block) -> bool {
+        if !self.chain.is_empty() {
+            println!("Ignoring starting block {}, the chain already has a genesis block", starting_block.hash);
+            return false;
+        }
+
+        let starting_hash = starting_block.hash.clone();
+        if self.add_block_without_validation(starting_block).is_err() {
+            return false;
+        }
+
+        self.connect_orphans(&starting_hash);
+        true
     }
 
     pub fn get_length(&self) -> usize {
@@ -88,28 +480,64 @@ impl Blockchain {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chain::wallet::Wallet;
+    use crate::constants::{BLOCKCHAIN_DIFFICULTY, MINING_REWARD_AMOUNT, MINING_REWARD_DELAY};
+
+    fn create_blockchain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_genesis_block();
+
+        blockchain
+    }
+
+    fn create_block(index: u64, previous_block_hash: String, transactions: Vec<Transaction>, miner_address: String) -> Block {
+        let mut block_transactions = vec![Transaction::new_coinbase(miner_address.clone(), MINING_REWARD_AMOUNT, index)];
+        block_transactions.extend(transactions);
+
+        Block::new(index, previous_block_hash, block_transactions, miner_address)
+    }
+
+    fn mine(block: &mut Block) {
+        while !block.mine() {}
+    }
+
+    fn create_signed_transaction(sender: &Wallet, amount: u64, nonce: u64) -> Transaction {
+        let mut transaction = Transaction {
+            sender: sender.get_public_key(),
+            recipient: "recipient".to_string(),
+            amount,
+            fee: 0,
+            timestamp: 0,
+            nonce,
+            id: String::new(),
+            signature: None
+        };
+        transaction.id = transaction.create_id();
+        sender.create_signature(&mut transaction);
+
+        transaction
+    }
 
     #[test]
     fn test_constructor() {
-        let blockchain = Blockchain::new();
+        let blockchain = create_blockchain();
         assert_eq!(blockchain.chain.len(), 1);
 
         let genesis = blockchain.get_latest_block();
-        assert_eq!(genesis.difficulty, 2);
+        assert_eq!(genesis.difficulty, BLOCKCHAIN_DIFFICULTY);
         assert!(genesis.transactions.is_empty());
         assert_eq!(genesis.index, 0);
         assert_eq!(genesis.previous_block_hash, "0");
-        assert_eq!(genesis.hash, "");
-        assert_eq!(genesis.nonce, 0);
+        assert_eq!(genesis.hash, genesis.create_hash());
     }
 
     #[test]
     fn test_is_valid_new_block_success() {
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = create_blockchain();
         let prev_block = blockchain.get_latest_block();
-        let mut new_block = Block::new(prev_block.index + 1, prev_block.hash.clone(), vec![], "miner_address".to_string());
+        let mut new_block = create_block(prev_block.index + 1, prev_block.hash.clone(), vec![], "miner_address".to_string());
 
-        new_block.mine();
+        mine(&mut new_block);
 
         let is_valid = blockchain.is_valid_new_block(&new_block);
 
@@ -118,68 +546,445 @@ mod tests {
 
     #[test]
     fn test_is_valid_new_block_invalid_prev_hash() {
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = create_blockchain();
         let prev_block = blockchain.get_latest_block();
-        let mut new_block = Block::new(prev_block.index + 1, "invalidHash".to_string(), vec![], "miner_address".to_string());
+        let mut new_block = create_block(prev_block.index + 1, "invalidHash".to_string(), vec![], "miner_address".to_string());
 
-        new_block.mine();
+        mine(&mut new_block);
 
         let is_valid = blockchain.is_valid_new_block(&new_block);
 
-        assert_eq!(is_valid, BlockValidationType::Invalid);
+        assert_eq!(is_valid, BlockValidationType::Fork);
+        assert!(blockchain.block_tree.contains_orphan(&new_block.hash));
+    }
+
+    #[test]
+    fn test_unmined_orphan_is_not_stored() {
+        let mut blockchain = create_blockchain();
+        let prev_block = blockchain.get_latest_block();
+        let new_block = create_block(prev_block.index + 1, "invalidHash".to_string(), vec![], "miner_address".to_string());
+
+        let is_valid = blockchain.is_valid_new_block(&new_block);
+
+        assert_eq!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::InvalidProofOfWork));
+        assert!(!blockchain.add_orphan_block(new_block));
+        assert_eq!(blockchain.block_tree.get_orphan_count(), 0);
     }
 
     #[test]
     fn test_is_valid_new_block_invalid_index() {
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = create_blockchain();
         let prev_block = blockchain.get_latest_block();
-        let mut new_block = Block::new(prev_block.index + 10, prev_block.hash.clone(), vec![], "miner_address".to_string());
+        let mut new_block = create_block(prev_block.index + 10, prev_block.hash.clone(), vec![], "miner_address".to_string());
 
-        new_block.mine();
+        mine(&mut new_block);
 
         let is_valid = blockchain.is_valid_new_block(&new_block);
 
-        assert_eq!(is_valid, BlockValidationType::Invalid);
+        assert_eq!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::InvalidIndex));
     }
 
     #[test]
     fn test_is_valid_new_block_invalid_hash() {
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = create_blockchain();
         let prev_block = blockchain.get_latest_block();
-        let mut new_block = Block::new(prev_block.index + 1, prev_block.hash.clone(), vec![], "miner_address".to_string());
+        let mut new_block = create_block(prev_block.index + 1, prev_block.hash.clone(), vec![], "miner_address".to_string());
 
-        new_block.mine();
+        mine(&mut new_block);
         new_block.hash += "invalid";
 
         let is_valid = blockchain.is_valid_new_block(&new_block);
 
-        assert_eq!(is_valid, BlockValidationType::Invalid);
+        assert_eq!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::InvalidProofOfWork));
     }
 
     #[test]
     fn test_is_valid_new_block_invalid_hash_prefix() {
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = create_blockchain();
         let prev_block = blockchain.get_latest_block();
-        let mut new_block = Block::new(prev_block.index + 1, prev_block.hash.clone(), vec![], "miner_address".to_string());
+        let mut new_block = create_block(prev_block.index + 1, prev_block.hash.clone(), vec![], "miner_address".to_string());
 
-        new_block.mine();
+        mine(&mut new_block);
         new_block.hash = new_block.hash[1..].to_string();
 
         let is_valid = blockchain.is_valid_new_block(&new_block);
 
-        assert_eq!(is_valid, BlockValidationType::Invalid);
+        assert_eq!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::InvalidProofOfWork));
+    }
+
+    #[test]
+    fn test_is_valid_new_block_invalid_difficulty() {
+        let mut blockchain = create_blockchain();
+        let prev_block = blockchain.get_latest_block();
+        let mut new_block = create_block(prev_block.index + 1, prev_block.hash.clone(), vec![], "miner_address".to_string());
+
+        new_block.difficulty = blockchain.get_next_difficulty() - 1;
+        mine(&mut new_block);
+
+        let is_valid = blockchain.is_valid_new_block(&new_block);
+
+        assert_eq!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::InvalidDifficulty { expected: BLOCKCHAIN_DIFFICULTY, found: BLOCKCHAIN_DIFFICULTY - 1 }));
     }
 
     #[test]
     fn test_get_latest_block() {
-        let mut blockchain = Blockchain::new();
+        let mut blockchain = create_blockchain();
         let prev_block = blockchain.get_latest_block().clone();
-        let new_block = Block::new(prev_block.index + 1, prev_block.hash.clone(), vec![], "miner_address".to_string());
+        let mut new_block = create_block(prev_block.index + 1, prev_block.hash.clone(), vec![], "miner_address".to_string());
 
+        mine(&mut new_block);
         blockchain.add_block_to_chain(&new_block);
 
         let latest_block = blockchain.get_latest_block();
 
         assert_eq!(latest_block.index, new_block.index);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_heavier_side_chain_causes_reorg() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+
+        let mut main_block = create_block(1, genesis.hash.clone(), vec![], "miner_1".to_string());
+        mine(&mut main_block);
+        assert_eq!(blockchain.add_block_to_chain(&main_block), BlockValidationType::Valid);
+
+        let mut side_block_1 = create_block(1, genesis.hash.clone(), vec![], "miner_2".to_string());
+        mine(&mut side_block_1);
+        assert_eq!(blockchain.add_block_to_chain(&side_block_1), BlockValidationType::SideChain);
+        assert_eq!(blockchain.get_latest_block().hash, main_block.hash);
+
+        let mut side_block_2 = create_block(2, side_block_1.hash.clone(), vec![], "miner_2".to_string());
+        mine(&mut side_block_2);
+        assert_eq!(blockchain.add_block_to_chain(&side_block_2), BlockValidationType::Reorg);
+
+        assert_eq!(blockchain.get_length(), 3);
+        assert_eq!(blockchain.chain[1].hash, side_block_1.hash);
+        assert_eq!(blockchain.get_latest_block().hash, side_block_2.hash);
+    }
+
+    #[test]
+    fn test_known_block_is_rejected() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+        let mut new_block = create_block(1, genesis.hash.clone(), vec![], "miner_address".to_string());
+        mine(&mut new_block);
+
+        assert_eq!(blockchain.add_block_to_chain(&new_block), BlockValidationType::Valid);
+        assert_eq!(blockchain.add_block_to_chain(&new_block), BlockValidationType::Invalid(InvalidBlockReason::KnownBlock));
+        assert_eq!(blockchain.get_length(), 2);
+    }
+
+    #[test]
+    fn test_orphans_connect_when_parent_arrives() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+
+        let mut block_1 = create_block(1, genesis.hash.clone(), vec![], "miner_address".to_string());
+        mine(&mut block_1);
+        let mut block_2 = create_block(2, block_1.hash.clone(), vec![], "miner_address".to_string());
+        mine(&mut block_2);
+        let mut block_3 = create_block(3, block_2.hash.clone(), vec![], "miner_address".to_string());
+        mine(&mut block_3);
+
+        assert_eq!(blockchain.add_block_to_chain(&block_3), BlockValidationType::Fork);
+        assert_eq!(blockchain.add_block_to_chain(&block_2), BlockValidationType::Fork);
+        assert_eq!(blockchain.block_tree.get_orphan_count(), 2);
+
+        assert_eq!(blockchain.add_block_to_chain(&block_1), BlockValidationType::Valid);
+
+        assert_eq!(blockchain.block_tree.get_orphan_count(), 0);
+        assert_eq!(blockchain.get_length(), 4);
+        assert_eq!(blockchain.get_latest_block().hash, block_3.hash);
+    }
+
+    #[test]
+    fn test_orphans_connect_after_loading_chain() {
+        let source = create_blockchain();
+        let genesis = source.get_latest_block().clone();
+        let mut block_1 = create_block(1, genesis.hash.clone(), vec![], "miner_address".to_string());
+        mine(&mut block_1);
+        let mut block_2 = create_block(2, block_1.hash.clone(), vec![], "miner_address".to_string());
+        mine(&mut block_2);
+
+        let mut blockchain = Blockchain::new();
+        blockchain.add_orphan_block(block_2.clone());
+        blockchain.load_chain(vec![genesis, block_1]);
+
+        assert_eq!(blockchain.get_length(), 3);
+        assert_eq!(blockchain.get_latest_block().hash, block_2.hash);
+    }
+
+    #[test]
+    fn test_get_chain_update_after_reorg() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+
+        let mut main_block = create_block(1, genesis.hash.clone(), vec![], "miner_1".to_string());
+        mine(&mut main_block);
+        blockchain.add_block_to_chain(&main_block);
+        let previous_tip = blockchain.get_latest_block().hash.clone();
+
+        let mut side_block_1 = create_block(1, genesis.hash.clone(), vec![], "miner_2".to_string());
+        mine(&mut side_block_1);
+        let mut side_block_2 = create_block(2, side_block_1.hash.clone(), vec![], "miner_2".to_string());
+        mine(&mut side_block_2);
+        blockchain.add_block_to_chain(&side_block_1);
+        blockchain.add_block_to_chain(&side_block_2);
+
+        let update = blockchain.get_chain_update(&previous_tip);
+
+        assert_eq!(update.disconnected, vec![main_block]);
+        assert_eq!(update.connected, vec![side_block_1, side_block_2]);
+    }
+
+    #[test]
+    fn test_get_chain_update_from_empty_chain() {
+        let blockchain = create_blockchain();
+
+        let update = blockchain.get_chain_update("");
+
+        assert!(update.disconnected.is_empty());
+        assert_eq!(update.connected.len(), 1);
+    }
+
+    #[test]
+    fn test_block_overspending_balance_is_rejected() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+        let transaction = create_signed_transaction(&Wallet::new(), 10, 0);
+        let mut new_block = create_block(1, genesis.hash.clone(), vec![transaction], "miner_address".to_string());
+        mine(&mut new_block);
+
+        let block_validation_type = blockchain.add_block_to_chain(&new_block);
+
+        assert!(matches!(block_validation_type, BlockValidationType::Invalid(InvalidBlockReason::InvalidState(_))));
+        assert_eq!(blockchain.get_length(), 1);
+        assert_eq!(blockchain.state.get_balance("recipient"), 0);
+    }
+
+    #[test]
+    fn test_double_spend_within_block_is_rejected() {
+        let mut blockchain = create_blockchain();
+        let sender = Wallet::new();
+        for index in 1..=MINING_REWARD_DELAY + 1 {
+            let mut new_block = create_block(index, blockchain.get_tip_hash(), vec![], sender.address.clone());
+            mine(&mut new_block);
+            blockchain.add_block_to_chain(&new_block);
+        }
+        assert_eq!(blockchain.state.get_balance(&sender.address), MINING_REWARD_AMOUNT);
+
+        // The next block matures a second reward, so only two of the three spends are funded
+        let spends = (0..3).map(|nonce| create_signed_transaction(&sender, MINING_REWARD_AMOUNT, nonce)).collect();
+        let index = blockchain.get_length() as u64;
+        let mut new_block = create_block(index, blockchain.get_tip_hash(), spends, "miner_address".to_string());
+        mine(&mut new_block);
+
+        let block_validation_type = blockchain.add_block_to_chain(&new_block);
+
+        assert!(matches!(block_validation_type, BlockValidationType::Invalid(InvalidBlockReason::InvalidState(_))));
+        assert_eq!(blockchain.state.get_balance(&sender.address), MINING_REWARD_AMOUNT);
+        assert_eq!(blockchain.state.get_balance("recipient"), 0);
+    }
+
+    #[test]
+    fn test_block_timestamp_bounds() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+
+        let mut early_block = create_block(1, genesis.hash.clone(), vec![], "miner_address".to_string());
+        early_block.timestamp = genesis.timestamp - 1;
+        mine(&mut early_block);
+
+        let mut future_block = create_block(1, genesis.hash.clone(), vec![], "miner_address".to_string());
+        future_block.timestamp += MAX_FUTURE_BLOCK_TIME + 60;
+        mine(&mut future_block);
+
+        assert_eq!(blockchain.is_valid_new_block(&early_block), BlockValidationType::Invalid(InvalidBlockReason::TimestampTooEarly));
+        assert_eq!(blockchain.is_valid_new_block(&future_block), BlockValidationType::Invalid(InvalidBlockReason::TimestampTooFarInFuture));
+    }
+
+    #[test]
+    fn test_duplicate_transaction_is_rejected() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+        let transaction = create_signed_transaction(&Wallet::new(), 10, 0);
+        let mut new_block = create_block(1, genesis.hash.clone(), vec![transaction.clone(), transaction.clone()], "miner_address".to_string());
+        mine(&mut new_block);
+
+        let is_valid = blockchain.is_valid_new_block(&new_block);
+
+        assert_eq!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::DuplicateTransaction(transaction.id)));
+    }
+
+    #[test]
+    fn test_unsigned_transaction_is_rejected() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+        let mut transaction = create_signed_transaction(&Wallet::new(), 10, 0);
+        transaction.signature = None;
+        let mut new_block = create_block(1, genesis.hash.clone(), vec![transaction.clone()], "miner_address".to_string());
+        mine(&mut new_block);
+
+        let is_valid = blockchain.is_valid_new_block(&new_block);
+
+        assert_eq!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::InvalidSignature(transaction.id)));
+    }
+
+    #[test]
+    fn test_block_transaction_limits() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+        let transactions = (0..=MAX_BLOCK_TRANSACTIONS as u64).map(|nonce| create_signed_transaction(&Wallet::new(), 10, nonce)).collect();
+        let mut new_block = create_block(1, genesis.hash.clone(), transactions, "miner_address".to_string());
+        mine(&mut new_block);
+
+        let is_valid = blockchain.is_valid_new_block(&new_block);
+
+        assert_eq!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::TooManyTransactions));
+    }
+
+    #[test]
+    fn test_mismatched_merkle_root_is_rejected() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+        let mut new_block = create_block(1, genesis.hash.clone(), vec![], "miner_address".to_string());
+        new_block.transactions.push(create_signed_transaction(&Wallet::new(), 10, 0));
+        mine(&mut new_block);
+
+        let is_valid = blockchain.is_valid_new_block(&new_block);
+
+        assert_eq!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::InvalidMerkleRoot));
+    }
+
+    #[test]
+    fn test_block_without_coinbase_is_rejected() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+        let mut new_block = Block::new(1, genesis.hash.clone(), vec![], "miner_address".to_string());
+        mine(&mut new_block);
+
+        let is_valid = blockchain.is_valid_new_block(&new_block);
+
+        assert!(matches!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::InvalidCoinbase(_))));
+    }
+
+    #[test]
+    fn test_coinbase_overpaying_miner_is_rejected() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+        let coinbase = Transaction::new_coinbase("miner_address".to_string(), MINING_REWARD_AMOUNT + 1, 1);
+        let mut new_block = Block::new(1, genesis.hash.clone(), vec![coinbase], "miner_address".to_string());
+        mine(&mut new_block);
+
+        let is_valid = blockchain.is_valid_new_block(&new_block);
+
+        assert!(matches!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::InvalidCoinbase(_))));
+    }
+
+    #[test]
+    fn test_coinbase_matures_after_reward_delay() {
+        let mut blockchain = create_blockchain();
+        for index in 1..=MINING_REWARD_DELAY + 1 {
+            let mut new_block = create_block(index, blockchain.get_tip_hash(), vec![], "miner_address".to_string());
+            mine(&mut new_block);
+            assert_eq!(blockchain.add_block_to_chain(&new_block), BlockValidationType::Valid);
+
+            let expected_balance = if index > MINING_REWARD_DELAY { MINING_REWARD_AMOUNT } else { 0 };
+            assert_eq!(blockchain.state.get_balance("miner_address"), expected_balance);
+        }
+    }
+
+    #[test]
+    fn test_coinbase_must_collect_fees() {
+        let mut blockchain = create_blockchain();
+        let genesis = blockchain.get_latest_block().clone();
+        let mut transaction = create_signed_transaction(&Wallet::new(), 10, 0);
+        transaction.fee = 5;
+        let mut new_block = create_block(1, genesis.hash.clone(), vec![transaction], "miner_address".to_string());
+        mine(&mut new_block);
+
+        let is_valid = blockchain.is_valid_new_block(&new_block);
+
+        assert_eq!(is_valid, BlockValidationType::Invalid(InvalidBlockReason::InvalidCoinbase(format!("coinbase pays {} instead of {}", MINING_REWARD_AMOUNT, MINING_REWARD_AMOUNT + 5))));
+    }
+
+    #[test]
+    fn test_chain_spec_sets_block_reward() {
+        let mut spec = ChainSpec::default();
+        spec.rewards.block_reward = 20;
+        let mut blockchain = Blockchain::with_spec(spec);
+        let genesis = blockchain.create_genesis_block();
+
+        let mut default_reward_block = create_block(1, genesis.hash.clone(), vec![], "miner_address".to_string());
+        mine(&mut default_reward_block);
+        let mut spec_reward_block = Block::new(1, genesis.hash.clone(), vec![Transaction::new_coinbase("miner_address".to_string(), 20, 1)], "miner_address".to_string());
+        mine(&mut spec_reward_block);
+
+        assert_eq!(blockchain.is_valid_new_block(&default_reward_block), BlockValidationType::Invalid(InvalidBlockReason::InvalidCoinbase(format!("coinbase pays {} instead of 20", MINING_REWARD_AMOUNT))));
+        assert_eq!(blockchain.is_valid_new_block(&spec_reward_block), BlockValidationType::Valid);
+    }
+
+    #[test]
+    fn test_tampered_genesis_is_rejected() {
+        let mut spec = ChainSpec::default();
+        spec.genesis.allocations.insert("alice".to_string(), 100);
+        let blockchain = Blockchain::with_spec(spec);
+        let genesis = blockchain.get_genesis_block().clone();
+
+        let mut tampered_genesis = genesis.clone();
+        tampered_genesis.transactions[0].amount = 1_000;
+
+        assert!(blockchain.is_expected_genesis(&genesis));
+        assert_eq!(tampered_genesis.hash, genesis.hash);
+        assert!(!blockchain.is_expected_genesis(&tampered_genesis));
+    }
+
+    #[test]
+    fn test_genesis_allocation_is_credited_once() {
+        let mut spec = ChainSpec::default();
+        spec.genesis.allocations.insert("alice".to_string(), 100);
+        let mut blockchain = Blockchain::with_spec(spec.clone());
+        blockchain.create_genesis_block();
+
+        for index in 1..=spec.rewards.maturity + 1 {
+            let mut block = create_block(index, blockchain.get_tip_hash(), vec![], "miner_address".to_string());
+            mine(&mut block);
+            assert_eq!(blockchain.add_block_to_chain(&block), BlockValidationType::Valid);
+        }
+
+        let mut replayed = Blockchain::with_spec(spec);
+        replayed.load_chain(blockchain.chain.clone());
+
+        assert_eq!(blockchain.state.get_balance("alice"), 100);
+        assert_eq!(replayed.get_length(), blockchain.get_length());
+        assert_eq!(replayed.state.get_balance("alice"), 100);
+    }
+
+    #[test]
+    fn test_starting_block_is_only_loaded_once() {
+        let mut spec = ChainSpec::default();
+        spec.genesis.allocations.insert("alice".to_string(), 100);
+        let mut blockchain = Blockchain::with_spec(spec);
+        let genesis = blockchain.get_genesis_block().clone();
+
+        assert!(blockchain.load_starting_block(genesis.clone()));
+        assert!(!blockchain.load_starting_block(genesis));
+        assert_eq!(blockchain.get_length(), 1);
+        assert_eq!(blockchain.state.get_balance("alice"), 100);
+    }
+
+    #[test]
+    fn test_empty_chain_only_accepts_expected_genesis() {
+        let mut blockchain = Blockchain::new();
+        let mut other_genesis = Block::new(0, "0".to_string(), vec![], "miner_address".to_string());
+        mine(&mut other_genesis);
+
+        assert_eq!(blockchain.is_valid_new_block(&other_genesis), BlockValidationType::Invalid(InvalidBlockReason::UnexpectedGenesis));
+        assert_eq!(blockchain.add_block_to_chain(&other_genesis), BlockValidationType::Invalid(InvalidBlockReason::UnexpectedGenesis));
+        assert_eq!(blockchain.get_length(), 0);
+
+        let genesis = blockchain.get_genesis_block().clone();
+        assert_eq!(blockchain.add_block_to_chain(&genesis), BlockValidationType::Valid);
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use crate::constants::{BLOCKCHAIN_DIFFICULTY, DIFFICULTY_ADJUSTMENT_INTERVAL, GENESIS_MINER_ADDRESS, GENESIS_TIMESTAMP, MAX_DIFFICULTY, MAX_DIFFICULTY_ADJUSTMENT, MAX_SUPPLY, MIN_DIFFICULTY, MINING_REWARD_AMOUNT, MINING_REWARD_DELAY, REWARD_HALVING_INTERVAL, TAIL_EMISSION, TARGET_BLOCK_TIME};
+
+/*
+    Parameters that define a network, loaded from a JSON chain-spec file at startup.
+    Nodes only agree on blocks when they run with the same spec,
+    so separate networks can share one binary by using different specs.
+    Any field left out of the file falls back to the defaults in constants.rs.
+*/
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ChainSpec {
+    pub network_id: String,
+    pub genesis: GenesisSpec,
+    pub rewards: RewardSpec,
+    pub difficulty: DifficultySpec
+}
+
+// Allocations are balances credited by the genesis block.
+// The nonce and hash can be embedded so nodes do not have to mine the genesis block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct GenesisSpec {
+    pub timestamp: i64,
+    pub miner_address: String,
+    pub allocations: BTreeMap<String, u64>,
+    pub nonce: Option<u64>,
+    pub hash: Option<String>
+}
+
+// A halving interval of 0 keeps the block reward flat, the reward never falls below
+// the tail emission, and no more than max_supply coins are ever issued when it is set
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RewardSpec {
+    pub block_reward: u64,
+    pub maturity: u64,
+    pub halving_interval: u64,
+    pub max_supply: Option<u64>,
+    pub tail_emission: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct DifficultySpec {
+    pub initial: usize,
+    pub min: usize,
+    pub max: usize,
+    pub adjustment_interval: u64,
+    pub target_block_time: i64,
+    pub max_adjustment: i64
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self {
+            network_id: "mockchain".to_string(),
+            genesis: GenesisSpec::default(),
+            rewards: RewardSpec::default(),
+            difficulty: DifficultySpec::default()
+        }
+    }
+}
+
+impl Default for GenesisSpec {
+    fn default() -> Self {
+        Self {
+            timestamp: GENESIS_TIMESTAMP,
+            miner_address: GENESIS_MINER_ADDRESS.to_string(),
+            allocations: BTreeMap::new(),
+            nonce: None,
+            hash: None
+        }
+    }
+}
+
+impl Default for RewardSpec {
+    fn default() -> Self {
+        Self {
+            block_reward: MINING_REWARD_AMOUNT,
+            maturity: MINING_REWARD_DELAY,
+            halving_interval: REWARD_HALVING_INTERVAL,
+            max_supply: Some(MAX_SUPPLY),
+            tail_emission: TAIL_EMISSION
+        }
+    }
+}
+
+impl Default for DifficultySpec {
+    fn default() -> Self {
+        Self {
+            initial: BLOCKCHAIN_DIFFICULTY,
+            min: MIN_DIFFICULTY,
+            max: MAX_DIFFICULTY,
+            adjustment_interval: DIFFICULTY_ADJUSTMENT_INTERVAL,
+            target_block_time: TARGET_BLOCK_TIME,
+            max_adjustment: MAX_DIFFICULTY_ADJUSTMENT
+        }
+    }
+}
+
+impl ChainSpec {
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)?;
+        let spec: ChainSpec = serde_json::from_str(&json)?;
+        spec.validate()?;
+
+        Ok(spec)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.network_id.is_empty() {
+            return Err(anyhow!("Chain spec network id is empty"));
+        }
+
+        let allocated = self.genesis.allocations.values().try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(anyhow!("Chain spec genesis allocations overflow"))?;
+
+        if let Some(max_supply) = self.rewards.max_supply && allocated > max_supply {
+            return Err(anyhow!("Chain spec genesis allocations exceed the max supply of {}", max_supply));
+        }
+
+        let difficulty = &self.difficulty;
+        if difficulty.min > difficulty.initial || difficulty.initial > difficulty.max || difficulty.max > 255 {
+            return Err(anyhow!("Chain spec difficulty must satisfy min <= initial <= max <= 255"));
+        }
+
+        if difficulty.adjustment_interval < 2 || difficulty.target_block_time <= 0 || difficulty.max_adjustment < 0 {
+            return Err(anyhow!("Chain spec difficulty adjustment rules are invalid"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let spec: ChainSpec = serde_json::from_str(r#"{"network_id": "testnet", "rewards": {"block_reward": 25}}"#).unwrap();
+
+        assert_eq!(spec.network_id, "testnet");
+        assert_eq!(spec.rewards.block_reward, 25);
+        assert_eq!(spec.rewards.maturity, MINING_REWARD_DELAY);
+        assert_eq!(spec.difficulty, DifficultySpec::default());
+    }
+
+    #[test]
+    fn test_invalid_difficulty_rules_are_rejected() {
+        let mut spec = ChainSpec::default();
+        spec.difficulty.min = spec.difficulty.initial + 1;
+
+        assert!(spec.validate().is_err());
+        assert!(ChainSpec::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,26 @@
+use std::collections::HashMap;
+use crate::chain::block::Block;
+
+/*
+    Describes how the main chain changed after new blocks were added.
+    Disconnected blocks are ordered from the old tip backwards,
+    connected blocks are ordered from the fork point to the new tip.
+    Balances holds the new balance of every account those blocks touched.
+*/
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ChainUpdate {
+    pub disconnected: Vec<Block>,
+    pub connected: Vec<Block>,
+    pub balances: HashMap<String, u64>
+}
+
+impl ChainUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.disconnected.is_empty() && self.connected.is_empty()
+    }
+
+    pub fn is_reorg(&self) -> bool {
+        !self.disconnected.is_empty()
+    }
+}
@@ -0,0 +1,200 @@
+use crate::chain::block::{Block, BlockHeader};
+use crate::chain::chain_spec::DifficultySpec;
+
+/*
+    Difficulty is the number of leading zero bits required in a block hash,
+    which gives a 256-bit target that the hash must not exceed.
+    Each step doubles the work needed to mine a block, so the difficulty
+    moves by the number of doublings that brings the block time back on target.
+*/
+
+pub fn next_difficulty(chain: &[Block], spec: &DifficultySpec) -> usize {
+    next_difficulty_from(chain.len(), |index| (chain[index].timestamp, chain[index].difficulty), spec)
+}
+
+// Same rule applied to a header chain downloaded before its blocks
+pub fn next_header_difficulty(headers: &[BlockHeader], spec: &DifficultySpec) -> usize {
+    next_difficulty_from(headers.len(), |index| (headers[index].timestamp, headers[index].difficulty), spec)
+}
+
+// get_block returns the timestamp and difficulty of the block at an index
+fn next_difficulty_from(length: usize, get_block: impl Fn(usize) -> (i64, usize), spec: &DifficultySpec) -> usize {
+    if length == 0 {
+        return spec.initial;
+    }
+
+    let (last_timestamp, last_difficulty) = get_block(length - 1);
+    if !(length as u64).is_multiple_of(spec.adjustment_interval) {
+        return last_difficulty;
+    }
+
+    let (first_timestamp, _) = get_block(length - spec.adjustment_interval as usize);
+    let actual_timespan = last_timestamp - first_timestamp;
+    let expected_timespan = spec.target_block_time * (spec.adjustment_interval as i64 - 1);
+
+    retarget(spec, last_difficulty, actual_timespan, expected_timespan)
+}
+
+// Expected number of hashes needed to mine a block at the given difficulty
+pub fn block_work(difficulty: usize) -> u128 {
+    if difficulty >= 128 {
+        return u128::MAX;
+    }
+
+    1u128 << difficulty
+}
+
+// Largest big-endian 256-bit hash accepted at the given difficulty
+pub fn get_target(difficulty: usize) -> [u8; 32] {
+    let mut target = [0xffu8; 32];
+    for bit in 0..difficulty.min(256) {
+        target[bit / 8] &= !(0x80 >> (bit % 8));
+    }
+
+    target
+}
+
+// Both values are big-endian, so comparing the bytes compares the numbers
+pub fn meets_target(hash: &[u8; 32], target: &[u8; 32]) -> bool {
+    hash <= target
+}
+
+fn retarget(spec: &DifficultySpec, difficulty: usize, actual_timespan: i64, expected_timespan: i64) -> usize {
+    let (expected_timespan, actual_timespan) = (expected_timespan.max(0), actual_timespan.max(1));
+    let adjustment = if expected_timespan >= actual_timespan {
+        rounded_doublings(expected_timespan, actual_timespan, spec.max_adjustment)
+    } else {
+        -rounded_doublings(actual_timespan, expected_timespan, spec.max_adjustment)
+    };
+    let new_difficulty = (difficulty as i64 + adjustment).clamp(spec.min as i64, spec.max as i64) as usize;
+
+    if new_difficulty > difficulty {
+        println!("Blocks mined too quickly, increasing difficulty to {}", new_difficulty);
+    } else if new_difficulty < difficulty {
+        println!("Blocks mined too slowly, decreasing difficulty to {}", new_difficulty);
+    }
+
+    new_difficulty
+}
+
+// log2(larger / smaller) rounded to the nearest integer and capped at max, in integer math so every node agrees.
+// The ratio rounds up to k doublings when it is at least 2^(k - 0.5), that is when 2 * larger^2 >= 4^k * smaller^2.
+fn rounded_doublings(larger: i64, smaller: i64, max: i64) -> i64 {
+    let larger_squared = 2 * (larger as u128).pow(2);
+    let smaller_squared = (smaller as u128).pow(2);
+
+    let mut doublings = 0;
+    while doublings < max {
+        let shift = 2 * (doublings + 1) as u32;
+        if shift >= u128::BITS {
+            break;
+        }
+        match smaller_squared.checked_mul(1 << shift) {
+            Some(threshold) if larger_squared >= threshold => doublings += 1,
+            _ => break
+        }
+    }
+
+    doublings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{BLOCKCHAIN_DIFFICULTY, DIFFICULTY_ADJUSTMENT_INTERVAL, MAX_DIFFICULTY_ADJUSTMENT, MIN_DIFFICULTY, TARGET_BLOCK_TIME};
+
+    fn create_chain(length: usize, block_time: i64, difficulty: usize) -> Vec<Block> {
+        (0..length).map(|index| {
+            let mut block = Block::new(index as u64, "previousBlockHash".to_string(), vec![], "minerAddress".to_string());
+            block.timestamp = index as i64 * block_time;
+            block.difficulty = difficulty;
+            block
+        }).collect()
+    }
+
+    #[test]
+    fn test_genesis_difficulty() {
+        assert_eq!(next_difficulty(&[], &DifficultySpec::default()), BLOCKCHAIN_DIFFICULTY);
+    }
+
+    #[test]
+    fn test_difficulty_unchanged_between_adjustments() {
+        let chain = create_chain(DIFFICULTY_ADJUSTMENT_INTERVAL as usize - 1, 1, 3);
+
+        assert_eq!(next_difficulty(&chain, &DifficultySpec::default()), 3);
+    }
+
+    #[test]
+    fn test_difficulty_increases_when_blocks_too_fast() {
+        let chain = create_chain(DIFFICULTY_ADJUSTMENT_INTERVAL as usize, TARGET_BLOCK_TIME / 2, 3);
+
+        assert_eq!(next_difficulty(&chain, &DifficultySpec::default()), 4);
+    }
+
+    #[test]
+    fn test_difficulty_decreases_when_blocks_too_slow() {
+        let chain = create_chain(DIFFICULTY_ADJUSTMENT_INTERVAL as usize, TARGET_BLOCK_TIME * 2, 3);
+
+        assert_eq!(next_difficulty(&chain, &DifficultySpec::default()), 2);
+    }
+
+    #[test]
+    fn test_difficulty_unchanged_when_on_target() {
+        let chain = create_chain(DIFFICULTY_ADJUSTMENT_INTERVAL as usize, TARGET_BLOCK_TIME, 3);
+
+        assert_eq!(next_difficulty(&chain, &DifficultySpec::default()), 3);
+    }
+
+    #[test]
+    fn test_retarget_rounds_at_half_doubling() {
+        let spec = DifficultySpec::default();
+        let expected_timespan = TARGET_BLOCK_TIME * (DIFFICULTY_ADJUSTMENT_INTERVAL as i64 - 1);
+
+        // 90 / 64 is just below 2^0.5 and 90 / 63 just above it
+        assert_eq!(retarget(&spec, 10, 64, expected_timespan), 10);
+        assert_eq!(retarget(&spec, 10, 63, expected_timespan), 11);
+        // 127 / 90 is just below 2^0.5 and 128 / 90 just above it
+        assert_eq!(retarget(&spec, 10, 127, expected_timespan), 10);
+        assert_eq!(retarget(&spec, 10, 128, expected_timespan), 9);
+        // 90 / 32 is just below 2^1.5 and 90 / 31 just above it
+        assert_eq!(retarget(&spec, 10, 32, expected_timespan), 11);
+        assert_eq!(retarget(&spec, 10, 31, expected_timespan), 12);
+    }
+
+    #[test]
+    fn test_difficulty_adjustment_is_limited() {
+        let fast_chain = create_chain(DIFFICULTY_ADJUSTMENT_INTERVAL as usize, 0, 10);
+        let slow_chain = create_chain(DIFFICULTY_ADJUSTMENT_INTERVAL as usize, TARGET_BLOCK_TIME * 100, 10);
+
+        assert_eq!(next_difficulty(&fast_chain, &DifficultySpec::default()), 10 + MAX_DIFFICULTY_ADJUSTMENT as usize);
+        assert_eq!(next_difficulty(&slow_chain, &DifficultySpec::default()), 10 - MAX_DIFFICULTY_ADJUSTMENT as usize);
+    }
+
+    #[test]
+    fn test_block_work_grows_with_difficulty() {
+        assert_eq!(block_work(0), 1);
+        assert_eq!(block_work(1), 2);
+        assert_eq!(block_work(16), 65536);
+        assert_eq!(block_work(200), u128::MAX);
+    }
+
+    #[test]
+    fn test_target_has_leading_zero_bits() {
+        let target = get_target(12);
+
+        assert_eq!(target[0], 0x00);
+        assert_eq!(target[1], 0x0f);
+        assert_eq!(target[2], 0xff);
+        let mut hash = target;
+        assert!(meets_target(&hash, &target));
+        hash[1] = 0x10;
+        assert!(!meets_target(&hash, &target));
+    }
+
+    #[test]
+    fn test_difficulty_never_below_minimum() {
+        let chain = create_chain(DIFFICULTY_ADJUSTMENT_INTERVAL as usize, TARGET_BLOCK_TIME * 5, MIN_DIFFICULTY);
+
+        assert_eq!(next_difficulty(&chain, &DifficultySpec::default()), MIN_DIFFICULTY);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::chain::chain_spec::{ChainSpec, RewardSpec};
+
+/*
+    Coin issuance schedule.
+    Every block after genesis creates a subsidy that starts at the block reward
+    and halves every halving interval, without dropping below the tail emission.
+    Genesis allocations count towards the supply and issuance stops at the max supply,
+    so the subsidy of a block is the supply it adds on top of the previous block.
+*/
+
+pub fn block_subsidy(spec: &ChainSpec, height: u64) -> u64 {
+    if height == 0 {
+        return 0;
+    }
+
+    total_supply(spec, height) - total_supply(spec, height - 1)
+}
+
+// Coins issued by the genesis allocations and the subsidies of blocks 1 to height
+pub fn total_supply(spec: &ChainSpec, height: u64) -> u64 {
+    let allocated: u128 = spec.genesis.allocations.values().map(|amount| *amount as u128).sum();
+    let supply = allocated + scheduled_subsidies(&spec.rewards, height);
+
+    match spec.rewards.max_supply {
+        Some(max_supply) => supply.min(max_supply as u128) as u64,
+        None => supply.min(u64::MAX as u128) as u64
+    }
+}
+
+fn scheduled_subsidies(rewards: &RewardSpec, height: u64) -> u128 {
+    if rewards.halving_interval == 0 {
+        return era_subsidy(rewards, 0) as u128 * height as u128;
+    }
+
+    let mut total = 0u128;
+    let mut remaining = height;
+    let mut era = 0;
+    while remaining > 0 {
+        let subsidy = era_subsidy(rewards, era);
+        // Once the subsidy reaches the tail emission it stays flat for every later block
+        if subsidy == rewards.tail_emission {
+            return total + subsidy as u128 * remaining as u128;
+        }
+
+        let blocks = remaining.min(rewards.halving_interval);
+        total += subsidy as u128 * blocks as u128;
+        remaining -= blocks;
+        era += 1;
+    }
+
+    total
+}
+
+fn era_subsidy(rewards: &RewardSpec, era: u64) -> u64 {
+    let halved = if era >= u64::BITS as u64 { 0 } else { rewards.block_reward >> era };
+    halved.max(rewards.tail_emission)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_spec(halving_interval: u64, max_supply: Option<u64>, tail_emission: u64) -> ChainSpec {
+        let mut spec = ChainSpec::default();
+        spec.rewards.block_reward = 50;
+        spec.rewards.halving_interval = halving_interval;
+        spec.rewards.max_supply = max_supply;
+        spec.rewards.tail_emission = tail_emission;
+
+        spec
+    }
+
+    #[test]
+    fn test_subsidy_halves_every_interval() {
+        let spec = create_spec(10, None, 0);
+
+        assert_eq!(block_subsidy(&spec, 0), 0);
+        assert_eq!(block_subsidy(&spec, 1), 50);
+        assert_eq!(block_subsidy(&spec, 10), 50);
+        assert_eq!(block_subsidy(&spec, 11), 25);
+        assert_eq!(block_subsidy(&spec, 21), 12);
+        assert_eq!(block_subsidy(&spec, 1_000), 0);
+        assert_eq!(total_supply(&spec, 20), 750);
+    }
+
+    #[test]
+    fn test_tail_emission_keeps_subsidy_above_floor() {
+        let spec = create_spec(10, None, 5);
+
+        assert_eq!(block_subsidy(&spec, 31), 6);
+        assert_eq!(block_subsidy(&spec, 41), 5);
+        assert_eq!(block_subsidy(&spec, 1_000_000), 5);
+    }
+
+    #[test]
+    fn test_supply_stops_at_max_supply() {
+        let mut spec = create_spec(0, Some(1_120), 0);
+        spec.genesis.allocations.insert("alice".to_string(), 1_000);
+
+        assert_eq!(total_supply(&spec, 0), 1_000);
+        assert_eq!(block_subsidy(&spec, 2), 50);
+        assert_eq!(block_subsidy(&spec, 3), 20);
+        assert_eq!(block_subsidy(&spec, 4), 0);
+        assert_eq!(total_supply(&spec, u64::MAX), 1_120);
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::{anyhow, Result};
+use crate::chain::block::Block;
+use crate::chain::chain_spec::ChainSpec;
+use crate::chain::transaction::Transaction;
+
+/*
+    The genesis block is built from the chain spec alone,
+    so every node on a network derives exactly the same block.
+    Premine allocations are coinbase transactions in the genesis block
+    and are credited straight away instead of after the maturity delay.
+    A spec can embed the genesis nonce and hash so nodes skip mining it,
+    and a node refuses to start if its spec produces a different block.
+*/
+
+pub fn build_genesis_block(spec: &ChainSpec) -> Result<Block> {
+    let genesis_spec = &spec.genesis;
+    let allocations = genesis_spec.allocations
+        .iter()
+        .map(|(address, amount)| {
+            let mut allocation = Transaction::new_coinbase(address.clone(), *amount, 0);
+            allocation.timestamp = genesis_spec.timestamp;
+            allocation.id = allocation.create_id();
+            allocation
+        })
+        .collect();
+
+    let mut genesis = Block::new(0, "0".to_string(), allocations, genesis_spec.miner_address.clone());
+    genesis.timestamp = genesis_spec.timestamp;
+    genesis.difficulty = spec.difficulty.initial;
+
+    match genesis_spec.nonce {
+        Some(nonce) => {
+            genesis.nonce = nonce;
+            genesis.hash = genesis.create_hash();
+            if !genesis.has_valid_proof_of_work() {
+                return Err(anyhow!("Genesis nonce {} does not meet difficulty {}", nonce, genesis.difficulty));
+            }
+        }
+        None => while !genesis.mine() {}
+    }
+
+    if let Some(expected_hash) = &genesis_spec.hash
+        && genesis.hash != *expected_hash {
+        return Err(anyhow!("Genesis block hash {} does not match chain spec hash {}", genesis.hash, expected_hash));
+    }
+
+    Ok(genesis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genesis_is_deterministic() {
+        let mut spec = ChainSpec::default();
+        spec.genesis.allocations.insert("alice".to_string(), 100);
+
+        let genesis = build_genesis_block(&spec).unwrap();
+
+        assert_eq!(genesis, build_genesis_block(&spec).unwrap());
+        assert_eq!(genesis.transactions.len(), 1);
+        assert_eq!(genesis.transactions[0].recipient, "alice");
+    }
+
+    #[test]
+    fn test_embedded_genesis_hash_is_checked() {
+        let mut spec = ChainSpec::default();
+        let genesis = build_genesis_block(&spec).unwrap();
+        spec.genesis.nonce = Some(genesis.nonce);
+        spec.genesis.hash = Some(genesis.hash.clone());
+        assert_eq!(build_genesis_block(&spec).unwrap(), genesis);
+
+        spec.genesis.allocations.insert("alice".to_string(), 100);
+        assert!(build_genesis_block(&spec).is_err());
+    }
+
+    #[test]
+    fn test_example_spec_genesis_matches_embedded_hash() {
+        let spec = ChainSpec::load(std::path::Path::new("chain-spec.example.json")).unwrap();
+
+        assert!(build_genesis_block(&spec).is_ok());
+    }
+}
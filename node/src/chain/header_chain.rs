@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use chrono::Utc;
+use hex::encode;
+use crate::chain::block::{Block, BlockHeader};
+use crate::chain::block_validation_type::InvalidBlockReason;
+use crate::chain::blockchain::Blockchain;
+use crate::chain::chain_spec::DifficultySpec;
+use crate::chain::difficulty::{block_work, get_target, meets_target, next_header_difficulty};
+use crate::constants::{MAX_FUTURE_BLOCK_TIME, MEDIAN_TIME_BLOCKS};
+
+/*
+    Chain of block headers built during initial block download.
+    Headers carry the proof-of-work, so a peer's chain can be checked and its work
+    measured before any block bodies are downloaded.
+    The first header is the local genesis block, which is trusted.
+*/
+
+#[derive(Clone, Debug)]
+pub struct HeaderChain {
+    pub headers: Vec<BlockHeader>,
+    pub hashes: Vec<String>,
+    positions: HashMap<String, usize>
+}
+
+impl HeaderChain {
+    pub fn from_blocks(blocks: &[Block]) -> Self {
+        let mut header_chain = Self { headers: vec![], hashes: vec![], positions: HashMap::new() };
+        for block in blocks {
+            header_chain.push(block.get_header(), block.hash.clone());
+        }
+
+        header_chain
+    }
+
+    // Header checks from Blockchain::check_block that do not need the transactions
+    pub fn append(&mut self, header: BlockHeader, spec: &DifficultySpec) -> Result<(), InvalidBlockReason> {
+        let parent = self.headers.last().ok_or(InvalidBlockReason::InvalidParent)?;
+        if header.previous_block_hash != self.get_tip_hash() {
+            return Err(InvalidBlockReason::InvalidParent);
+        }
+
+        if header.index != parent.index + 1 {
+            return Err(InvalidBlockReason::InvalidIndex);
+        }
+
+        let hash = header.hash();
+        if !meets_target(&hash, &get_target(header.difficulty)) {
+            return Err(InvalidBlockReason::InvalidProofOfWork);
+        }
+
+        let expected_difficulty = next_header_difficulty(&self.headers, spec);
+        if header.difficulty != expected_difficulty {
+            return Err(InvalidBlockReason::InvalidDifficulty { expected: expected_difficulty, found: header.difficulty });
+        }
+
+        let recent_headers = &self.headers[self.headers.len().saturating_sub(MEDIAN_TIME_BLOCKS)..];
+        if header.timestamp < Blockchain::get_median_timestamp(recent_headers.iter().map(|header| header.timestamp).collect()) {
+            return Err(InvalidBlockReason::TimestampTooEarly);
+        }
+
+        if header.timestamp > Utc::now().timestamp() + MAX_FUTURE_BLOCK_TIME {
+            return Err(InvalidBlockReason::TimestampTooFarInFuture);
+        }
+
+        self.push(header, encode(hash));
+        Ok(())
+    }
+
+    // Drops every header after the given one, returns false if the hash is unknown
+    pub fn truncate_after(&mut self, hash: &str) -> bool {
+        let Some(&position) = self.positions.get(hash) else {
+            return false;
+        };
+
+        for removed in self.hashes.drain(position + 1..) {
+            self.positions.remove(&removed);
+        }
+        self.headers.truncate(position + 1);
+
+        true
+    }
+
+    // Recent hashes first, then exponentially further back, always ending at genesis
+    pub fn get_locator(&self) -> Vec<String> {
+        let mut locator = vec![];
+        let mut index = self.hashes.len() as i64 - 1;
+        let mut step = 1;
+        while index > 0 {
+            locator.push(self.hashes[index as usize].clone());
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            index -= step;
+        }
+
+        if let Some(genesis_hash) = self.hashes.first() {
+            locator.push(genesis_hash.clone());
+        }
+
+        locator
+    }
+
+    pub fn get_chain_work(&self) -> u128 {
+        self.headers.iter().fold(0u128, |work, header| work.saturating_add(block_work(header.difficulty)))
+    }
+
+    pub fn get_position(&self, hash: &str) -> Option<usize> {
+        self.positions.get(hash).copied()
+    }
+
+    pub fn get_tip_hash(&self) -> String {
+        self.hashes.last().cloned().unwrap_or_default()
+    }
+
+    pub fn get_length(&self) -> usize {
+        self.headers.len()
+    }
+
+    fn push(&mut self, header: BlockHeader, hash: String) {
+        self.positions.insert(hash.clone(), self.hashes.len());
+        self.headers.push(header);
+        self.hashes.push(hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::transaction::Transaction;
+
+    fn create_blocks(length: usize) -> Vec<Block> {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_genesis_block();
+        for index in 1..length as u64 {
+            let coinbase = Transaction::new_coinbase("miner_address".to_string(), blockchain.spec.rewards.block_reward, index);
+            let mut block = Block::new(index, blockchain.get_tip_hash(), vec![coinbase], "miner_address".to_string());
+            block.difficulty = blockchain.get_next_difficulty();
+            while !block.mine() {}
+            blockchain.add_block_to_chain(&block);
+        }
+
+        blockchain.chain
+    }
+
+    #[test]
+    fn test_append_validates_headers() {
+        let blocks = create_blocks(3);
+        let spec = DifficultySpec::default();
+        let mut header_chain = HeaderChain::from_blocks(&blocks[..1]);
+
+        let mut unlinked_header = blocks[2].get_header();
+        unlinked_header.index = 1;
+        assert_eq!(header_chain.append(unlinked_header, &spec), Err(InvalidBlockReason::InvalidParent));
+
+        let mut unmined_header = blocks[1].get_header();
+        unmined_header.nonce += 1;
+        assert!(header_chain.append(unmined_header, &spec).is_err());
+
+        assert_eq!(header_chain.append(blocks[1].get_header(), &spec), Ok(()));
+        assert_eq!(header_chain.append(blocks[2].get_header(), &spec), Ok(()));
+        assert_eq!(header_chain.get_tip_hash(), blocks[2].hash);
+    }
+
+    #[test]
+    fn test_locator_and_truncate() {
+        let blocks = create_blocks(3);
+        let mut header_chain = HeaderChain::from_blocks(&blocks);
+
+        assert_eq!(header_chain.get_locator(), vec![blocks[2].hash.clone(), blocks[1].hash.clone(), blocks[0].hash.clone()]);
+
+        assert!(header_chain.truncate_after(&blocks[0].hash));
+        assert_eq!(header_chain.get_length(), 1);
+        assert_eq!(header_chain.get_position(&blocks[1].hash), None);
+        assert!(!header_chain.truncate_after("unknown_hash"));
+    }
+}
@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::chain::transaction::Transaction;
+
+/*
+    Merkle tree over the signed transactions of a block.
+    Leaves hash the signed transaction hashes behind a LEAF_PREFIX byte and each level
+    hashes pairs of nodes behind a NODE_PREFIX byte, so a leaf can never pass as a node.
+    An odd node is carried up to the next level unchanged rather than paired with itself,
+    so repeating the last transaction always changes the root (CVE-2012-2459).
+    A proof lists the sibling of every node on the path from a leaf to the root,
+    so a client can confirm a transaction is in a block from the header alone.
+    The proof leaf is the signed transaction hash itself.
+*/
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProofStep {
+    pub hash: String,
+    pub is_left: bool
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MerkleProof {
+    pub leaf: String,
+    pub steps: Vec<ProofStep>
+}
+
+pub fn merkle_root(transactions: &[Transaction]) -> String {
+    let mut level = hash_leaves(transactions);
+    if level.is_empty() {
+        return hex::encode([0u8; 32]);
+    }
+
+    while level.len() > 1 {
+        level = hash_level(&level);
+    }
+
+    hex::encode(level[0])
+}
+
+pub fn create_proof(transactions: &[Transaction], transaction_id: &str) -> Option<MerkleProof> {
+    let mut position = transactions.iter().position(|transaction| transaction.id == transaction_id)?;
+    let leaf = hex::encode(transactions[position].signed_hash());
+    let mut level = hash_leaves(transactions);
+    let mut steps: Vec<ProofStep> = vec![];
+
+    while level.len() > 1 {
+        // A node without a sibling is carried up, so it adds no step
+        let sibling_position = position ^ 1;
+        if let Some(sibling) = level.get(sibling_position) {
+            steps.push(ProofStep {
+                hash: hex::encode(sibling),
+                is_left: sibling_position < position
+            });
+        }
+
+        level = hash_level(&level);
+        position /= 2;
+    }
+
+    Some(MerkleProof { leaf, steps })
+}
+
+pub fn verify_proof(proof: &MerkleProof, merkle_root: &str) -> bool {
+    let mut current = match decode_hash(&proof.leaf) {
+        Some(hash) => hash_leaf(&hash),
+        None => return false
+    };
+
+    for step in &proof.steps {
+        let sibling = match decode_hash(&step.hash) {
+            Some(hash) => hash,
+            None => return false
+        };
+
+        current = if step.is_left {
+            hash_pair(&sibling, &current)
+        } else {
+            hash_pair(&current, &sibling)
+        };
+    }
+
+    hex::encode(current) == merkle_root
+}
+
+fn hash_leaves(transactions: &[Transaction]) -> Vec<[u8; 32]> {
+    transactions.iter().map(|transaction| hash_leaf(&transaction.signed_hash())).collect()
+}
+
+fn hash_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level.chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_pair(left, right),
+            [odd] => *odd,
+            _ => unreachable!("Chunks hold one or two nodes")
+        })
+        .collect()
+}
+
+fn hash_leaf(leaf: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(leaf);
+    hasher.finalize().into()
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn decode_hash(hash: &str) -> Option<[u8; 32]> {
+    hex::decode(hash).ok()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_transactions(count: u64) -> Vec<Transaction> {
+        (0..count).map(|nonce| {
+            let mut transaction = Transaction {
+                sender: "sender".to_string(),
+                recipient: "recipient".to_string(),
+                amount: 10,
+                fee: 0,
+                timestamp: 0,
+                nonce,
+                id: String::new(),
+                signature: None
+            };
+            transaction.id = transaction.create_id();
+            transaction
+        }).collect()
+    }
+
+    #[test]
+    fn test_proofs_verify_for_every_transaction() {
+        for count in 1..8 {
+            let transactions = create_transactions(count);
+            let root = merkle_root(&transactions);
+
+            for transaction in &transactions {
+                let proof = create_proof(&transactions, &transaction.id).unwrap();
+                assert_eq!(proof.leaf, hex::encode(transaction.signed_hash()));
+                assert!(verify_proof(&proof, &root));
+            }
+        }
+    }
+
+    #[test]
+    fn test_proof_fails_against_other_root() {
+        let transactions = create_transactions(3);
+        let proof = create_proof(&transactions, &transactions[1].id).unwrap();
+
+        assert!(!verify_proof(&proof, &merkle_root(&transactions[..2])));
+    }
+
+    #[test]
+    fn test_root_changes_with_transactions() {
+        let mut transactions = create_transactions(4);
+        let root = merkle_root(&transactions);
+        transactions[3].amount = 20;
+
+        assert_ne!(merkle_root(&transactions), root);
+        assert_eq!(merkle_root(&[]), hex::encode([0u8; 32]));
+    }
+
+    #[test]
+    fn test_repeated_last_transaction_changes_root() {
+        let transactions = create_transactions(3);
+        let mut repeated = transactions.clone();
+        repeated.push(transactions[2].clone());
+
+        assert_ne!(merkle_root(&transactions), merkle_root(&repeated));
+    }
+
+    #[test]
+    fn test_single_leaf_root_is_not_the_leaf() {
+        let transactions = create_transactions(1);
+
+        assert_ne!(merkle_root(&transactions), hex::encode(transactions[0].signed_hash()));
+    }
+
+    #[test]
+    fn test_no_proof_for_unknown_transaction() {
+        assert!(create_proof(&create_transactions(2), "unknown").is_none());
+    }
+}
@@ -1,5 +1,14 @@
+pub mod account_state;
 pub mod block;
+pub mod block_tree;
 pub mod blockchain;
 pub mod block_validation_type;
+pub mod chain_spec;
+pub mod chain_update;
+pub mod difficulty;
+pub mod emission;
+pub mod genesis;
+pub mod header_chain;
+pub mod merkle;
 pub mod transaction;
 pub mod wallet;
\ No newline at end of file
@@ -2,18 +2,38 @@ use std::str::FromStr;
 use secp256k1::ecdsa::Signature;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use chrono::Utc;
+use crate::constants::COINBASE_SENDER;
 use crate::server::request::transaction::TransactionRequest;
 
+/*
+    Transactions are sent and stored with their id and signature.
+    The id and the signed message are both derived from the hash of the
+    unsigned fields, so a transaction can be verified by any node.
+    The first transaction of every mined block is an unsigned coinbase
+    paying the block reward to the miner, its nonce is the block index.
+*/
+
+#[derive(Serialize)]
+struct UnsignedTransaction<'a> {
+    sender: &'a str,
+    recipient: &'a str,
+    amount: u64,
+    fee: u64,
+    timestamp: i64,
+    nonce: u64
+}
+
 #[derive(PartialEq, Serialize, Deserialize, Clone, Debug)]
 pub struct Transaction {
     pub sender: String,
     pub recipient: String,
     pub amount: u64,
+    #[serde(default)]
+    pub fee: u64,
     pub timestamp: i64,
-
-    #[serde(skip_serializing, skip_deserializing)]
+    pub nonce: u64,
     pub id: String,
-    #[serde(skip_serializing, skip_deserializing)]
     pub signature: Option<Signature>
 }
 
@@ -23,14 +43,46 @@ impl Transaction {
             sender: transaction_data.sender_public_key,
             recipient: transaction_data.recipient_address,
             amount: transaction_data.amount,
+            fee: transaction_data.fee,
             timestamp: transaction_data.timestamp,
+            nonce: transaction_data.nonce,
             id: transaction_data.id,
             signature: Some(Signature::from_str(transaction_data.signature.as_str()).unwrap())
         }
     }
 
+    pub fn new_coinbase(recipient_address: String, amount: u64, block_index: u64) -> Self {
+        let mut transaction = Self {
+            sender: COINBASE_SENDER.to_string(),
+            recipient: recipient_address,
+            amount,
+            fee: 0,
+            timestamp: Utc::now().timestamp(),
+            nonce: block_index,
+            id: String::new(),
+            signature: None
+        };
+        transaction.id = transaction.create_id();
+
+        transaction
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.sender == COINBASE_SENDER
+    }
+
+    // Bytes covered by the signature, the id and signature themselves are excluded
     pub fn to_bytes(&self) -> Vec<u8> {
-        serde_json::to_vec(self).expect("Failed to serialize transaction")
+        let unsigned_transaction = UnsignedTransaction {
+            sender: &self.sender,
+            recipient: &self.recipient,
+            amount: self.amount,
+            fee: self.fee,
+            timestamp: self.timestamp,
+            nonce: self.nonce
+        };
+
+        serde_json::to_vec(&unsigned_transaction).expect("Failed to serialize transaction")
     }
 
     pub fn hash(&self) -> [u8; 32] {
@@ -38,4 +90,18 @@ impl Transaction {
         hasher.update(self.to_bytes());
         hasher.finalize().into()
     }
-}
\ No newline at end of file
+
+    // Hash of the full transaction including its signature, used as a Merkle leaf
+    pub fn signed_hash(&self) -> [u8; 32] {
+        let serialized = serde_json::to_vec(self).expect("Failed to serialize transaction");
+        Sha256::digest(serialized).into()
+    }
+
+    pub fn get_size(&self) -> usize {
+        serde_json::to_vec(self).expect("Failed to serialize transaction").len()
+    }
+
+    pub fn create_id(&self) -> String {
+        hex::encode(self.hash())
+    }
+}
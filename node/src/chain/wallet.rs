@@ -45,6 +45,18 @@ impl Wallet {
         }
     }
 
+    // Returns None instead of panicking when the key comes from an untrusted peer
+    pub fn try_load_from_public_key(public_key_str: &str) -> Option<Self> {
+        let bytes = hex::decode(public_key_str).ok()?;
+        let public_key = PublicKey::from_slice(&bytes).ok()?;
+
+        Some(Self {
+            private_key: None,
+            public_key,
+            address: Self::derive_address_hash(&public_key)
+        })
+    }
+
     pub fn derive_address_hash_from_string(public_key: &String) -> String {
         Self::derive_address_hash(&Self::public_key_from_hex(public_key.as_str()))
     }
@@ -61,11 +73,28 @@ impl Wallet {
     }
 
     pub fn verify_signature(&self, transaction: &Transaction) -> bool {
+        let signature = match transaction.signature {
+            Some(signature) => signature,
+            None => return false
+        };
+
+        if transaction.id != transaction.create_id() {
+            return false;
+        }
+
         let secp = Secp256k1::verification_only();
         let tx_hash = transaction.hash();
         let message = Message::from_digest(tx_hash);
 
-        secp.verify_ecdsa(message, &transaction.signature.unwrap(), &self.public_key).is_ok()
+        secp.verify_ecdsa(message, &signature, &self.public_key).is_ok()
+    }
+
+    pub fn create_signature(&self, transaction: &mut Transaction) {
+        let secp = Secp256k1::new();
+        let tx_hash = transaction.hash();
+        let message = Message::from_digest(tx_hash);
+
+        transaction.signature = Some(secp.sign_ecdsa(message, &self.private_key.expect("Wallet has no private key")));
     }
 
     pub fn get_public_key(&self) -> String {
@@ -1,7 +1,82 @@
+// Coinbase outputs can only be spent once MINING_REWARD_DELAY more blocks have been mined
 pub const MINING_REWARD_DELAY: u64 = 5;
 pub const MINING_REWARD_AMOUNT: u64 = 50;
+// The block reward halves every REWARD_HALVING_INTERVAL blocks until MAX_SUPPLY has been issued
+pub const REWARD_HALVING_INTERVAL: u64 = 210_000;
+pub const MAX_SUPPLY: u64 = 21_000_000;
+pub const TAIL_EMISSION: u64 = 0;
+pub const COINBASE_SENDER: &str = "coinbase";
 
+// Genesis block fields used when the chain spec does not set them
+pub const GENESIS_TIMESTAMP: i64 = 1_758_000_000;
+pub const GENESIS_MINER_ADDRESS: &str = "0000000000000000000000000000000000000000";
+
+// Difficulty is the number of leading zero bits required in a block hash
 #[cfg(any(test, feature = "mock"))]
-pub const BLOCKCHAIN_DIFFICULTY: usize = 4;
+pub const BLOCKCHAIN_DIFFICULTY: usize = 16;
 #[cfg(not(any(test, feature = "mock")))]
-pub const BLOCKCHAIN_DIFFICULTY: usize = 5;
\ No newline at end of file
+pub const BLOCKCHAIN_DIFFICULTY: usize = 20;
+
+// Difficulty is recalculated every DIFFICULTY_ADJUSTMENT_INTERVAL blocks
+// so that blocks are mined roughly every TARGET_BLOCK_TIME seconds
+pub const DIFFICULTY_ADJUSTMENT_INTERVAL: u64 = 10;
+pub const TARGET_BLOCK_TIME: i64 = 10;
+pub const MIN_DIFFICULTY: usize = 1;
+pub const MAX_DIFFICULTY: usize = 255;
+// Difficulty moves by at most MAX_DIFFICULTY_ADJUSTMENT bits, a factor of 4, per retarget
+pub const MAX_DIFFICULTY_ADJUSTMENT: i64 = 2;
+
+// Blocks more than FINALITY_DEPTH below the tip are final, their undo records
+// are dropped and a reorganisation that would revert them is refused
+pub const FINALITY_DEPTH: u64 = 100;
+
+// At most MAX_ORPHAN_BLOCKS orphans are held, each for up to ORPHAN_EXPIRY seconds
+pub const MAX_ORPHAN_BLOCKS: usize = 100;
+pub const ORPHAN_EXPIRY: i64 = 20 * 60;
+
+// A block timestamp may not be earlier than the median of the previous
+// MEDIAN_TIME_BLOCKS blocks or more than MAX_FUTURE_BLOCK_TIME seconds ahead
+pub const MEDIAN_TIME_BLOCKS: usize = 11;
+pub const MAX_FUTURE_BLOCK_TIME: i64 = 2 * 60 * 60;
+
+pub const MAX_BLOCK_TRANSACTIONS: usize = 1000;
+pub const MAX_BLOCK_SIZE: usize = 1_000_000;
+// Space left for transactions once the header and coinbase are accounted for
+pub const MAX_BLOCK_TRANSACTIONS_SIZE: usize = MAX_BLOCK_SIZE - 10_000;
+
+pub const MAX_MEMPOOL_TRANSACTIONS: usize = 5000;
+pub const MAX_SEEN_TRANSACTIONS: usize = 20_000;
+// Pending transactions are dropped after MEMPOOL_EXPIRY seconds, checked every MEMPOOL_EXPIRY_CHECK_INTERVAL seconds
+pub const MEMPOOL_EXPIRY: i64 = 72 * 60 * 60;
+pub const MEMPOOL_EXPIRY_CHECK_INTERVAL: u64 = 60;
+
+// Hashrate is reported every HASHRATE_REPORT_INTERVAL seconds. Miners try MINING_BATCH_SIZE nonces
+// between checks for cancellation and add the batch to the shared hash count at once.
+pub const HASHRATE_REPORT_INTERVAL: u64 = 10;
+pub const MINING_BATCH_SIZE: u64 = 4096;
+
+// Jobs handed to external miners, only the most recent MAX_MINING_JOBS are kept
+pub const MAX_MINING_JOBS: usize = 100;
+
+// Initial block download fetches headers in messages of up to MAX_HEADERS_PER_MESSAGE,
+// then block bodies in batches of BLOCK_DOWNLOAD_BATCH_SIZE spread across peers.
+// A batch of maximum size blocks still fits in one message.
+pub const MAX_HEADERS_PER_MESSAGE: usize = 2000;
+pub const BLOCK_DOWNLOAD_BATCH_SIZE: usize = MAX_MESSAGE_SIZE / MAX_BLOCK_SIZE - 1;
+pub const MAX_BLOCK_DOWNLOAD_ATTEMPTS: usize = 3;
+
+// Peers exchange frames that start with NETWORK_MAGIC and carry at most MAX_MESSAGE_SIZE bytes
+pub const NETWORK_MAGIC: [u8; 4] = *b"MOCK";
+pub const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+// Connections are closed when the peer's protocol version is below MIN_PROTOCOL_VERSION
+// or its version message does not arrive within HANDSHAKE_TIMEOUT seconds
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+pub const HANDSHAKE_TIMEOUT: u64 = 10;
+// A request fails when the peer has not responded after REQUEST_TIMEOUT seconds
+pub const REQUEST_TIMEOUT: u64 = 30;
+
+// Services a node advertises in its version message
+pub const SERVICE_BLOCKS: u64 = 1;
+pub const SERVICE_API: u64 = 1 << 1;
+pub const SERVICE_MINING: u64 = 1 << 2;
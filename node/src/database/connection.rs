@@ -2,15 +2,15 @@ use sqlx::{Error, Pool, Postgres};
 use sqlx::migrate::MigrateDatabase;
 use sqlx::postgres::PgPoolOptions;
 use uuid::Uuid;
-use crate::constants::MINING_REWARD_AMOUNT;
+use crate::chain::block::Block;
 use crate::database::operations::DatabaseOperations;
-use crate::database::structs::recipient_address::RecipientAddress;
+use crate::database::structs::block_data::BlockData;
 use crate::database::structs::user_balance::UserBalance;
-use crate::mining::mining_reward::MiningReward;
 
 pub struct Connection {
     pub pool: Pool<Postgres>,
-    db_name: String
+    db_name: String,
+    persistent: bool
 }
 
 #[async_trait::async_trait]
@@ -31,16 +31,24 @@ impl DatabaseOperations for Connection {
         self.update_user_balance(user_address, amount).await
     }
 
-    async fn save_mining_reward(&self, mining_reward: MiningReward) -> bool {
-        self.save_mining_reward(mining_reward).await
+    async fn set_user_balance(&self, user_address: String, balance: u64) -> bool {
+        self.set_user_balance(user_address, balance).await
     }
 
-    async fn get_mining_reward_at_block_index(&self, block_index: u64) -> anyhow::Result<RecipientAddress> {
-        self.get_mining_reward_at_block_index(block_index).await
+    async fn save_block(&self, block: &Block) -> bool {
+        self.save_block(block).await
     }
 
-    async fn create_user_and_update_balance(&self, recipient_address: String, amount: i64) {
-        self.create_user_and_update_balance(recipient_address, amount).await;
+    async fn delete_blocks_from_index(&self, block_index: u64) -> bool {
+        self.delete_blocks_from_index(block_index).await
+    }
+
+    async fn get_blocks(&self) -> anyhow::Result<Vec<Block>> {
+        self.get_blocks().await
+    }
+
+    fn is_persistent(&self) -> bool {
+        self.persistent
     }
 
     async fn drop_database(&self) {
@@ -55,10 +63,16 @@ impl DatabaseOperations for Connection {
 impl Connection {
 
     pub async fn new() -> Self {
-        let db_name = format!("devconnor_blockchain_{}", Uuid::new_v4());
+        // A named database outlives the node so the chain can be reloaded on restart
+        let (db_name, persistent) = match std::env::var("POSTGRES_DATABASE") {
+            Ok(db_name) => (db_name, true),
+            Err(_) => (format!("devconnor_blockchain_{}", Uuid::new_v4()), false)
+        };
         let db_url = Self::get_db_url(db_name.clone());
 
-        Self::create_database(db_url.clone()).await;
+        if !Postgres::database_exists(db_url.as_str()).await.unwrap_or(false) {
+            Self::create_database(db_url.clone()).await;
+        }
 
         let db_pool = PgPoolOptions::new()
             .max_connections(1)
@@ -68,7 +82,7 @@ impl Connection {
 
         sqlx::migrate!().run(&db_pool).await.expect("Migrating database failed");
 
-        Self {pool: db_pool, db_name}
+        Self {pool: db_pool, db_name, persistent}
     }
 
     fn get_db_url(db_name: String) -> String {
@@ -154,51 +168,84 @@ impl Connection {
         db_response.is_ok()
     }
 
-    pub async fn save_mining_reward(&self, mining_reward: MiningReward) -> bool {
-        println!("Saving mining reward to database");
+    pub async fn set_user_balance(&self, user_address: String, balance: u64) -> bool {
         let db_response = sqlx::query(
             r#"
-            INSERT INTO rewards (recipient_address, amount, block_unlocked_at)
-            VALUES ($1, $2, $3)
+            UPDATE users
+            SET balance = $1
+            WHERE address = $2
             "#
         )
-        .bind(mining_reward.recipient_address)
-        .bind(mining_reward.amount as i64)
-        .bind(mining_reward.block_unlocked_at as i64)
+        .bind(balance as i64)
+        .bind(&user_address)
         .execute(&self.pool)
         .await;
 
-        if db_response.is_err() {
-            println!("There was an error when saving mining reward: {}", db_response.unwrap_err());
-            return false
+        match db_response {
+            Ok(result) if result.rows_affected() == 0 => self.create_user(user_address, balance).await,
+            Ok(_) => true,
+            Err(e) => {
+                println!("ERROR when setting user balance: {}", e);
+                false
+            }
         }
-
-        db_response.is_ok()
     }
 
-    pub async fn get_mining_reward_at_block_index(&self, block_index: u64) -> anyhow::Result<RecipientAddress> {
-        let reward_retrieved: Result<RecipientAddress, Error> = sqlx::query_as(
-            "SELECT recipient_address FROM rewards WHERE block_unlocked_at = $1"
+    pub async fn save_block(&self, block: &Block) -> bool {
+        let block_data = serde_json::to_string(block).expect("Failed to serialize block");
+        let db_response = sqlx::query(
+            r#"
+            INSERT INTO blocks (block_index, hash, previous_block_hash, data)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (block_index) DO UPDATE
+            SET hash = EXCLUDED.hash, previous_block_hash = EXCLUDED.previous_block_hash, data = EXCLUDED.data
+            "#
         )
-        .bind(block_index as i64)
-        .fetch_one(&self.pool)
+        .bind(block.index as i64)
+        .bind(&block.hash)
+        .bind(&block.previous_block_hash)
+        .bind(block_data)
+        .execute(&self.pool)
         .await;
 
-        match reward_retrieved {
-            Ok(address) => Ok(address),
-            Err(e) => {
-                println!("There was an error retrieving mining reward: {}", e);
-                Err(anyhow::anyhow!("Mining reward not found"))
-            }
+        if let Err(e) = db_response {
+            println!("There was an error when saving block {}: {}", block.index, e);
+            return false;
         }
+
+        true
     }
 
-    pub async fn create_user_and_update_balance(&self, recipient_address: String, amount: i64) {
-        let user_exists = self.create_user_if_not_exists(&recipient_address, MINING_REWARD_AMOUNT).await;
-        if user_exists {
-            // Increment user balance if already exists in db
-            // Otherwise, the balance will be saved on user creation
-            self.update_user_balance(recipient_address, amount).await;
+    pub async fn delete_blocks_from_index(&self, block_index: u64) -> bool {
+        let db_response = sqlx::query("DELETE FROM blocks WHERE block_index >= $1")
+            .bind(block_index as i64)
+            .execute(&self.pool)
+            .await;
+
+        if let Err(e) = db_response {
+            println!("There was an error when deleting blocks from index {}: {}", block_index, e);
+            return false;
+        }
+
+        true
+    }
+
+    pub async fn get_blocks(&self) -> anyhow::Result<Vec<Block>> {
+        let blocks_retrieved: Result<Vec<BlockData>, Error> = sqlx::query_as(
+            "SELECT data FROM blocks ORDER BY block_index"
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        match blocks_retrieved {
+            Ok(rows) => {
+                rows.iter()
+                    .map(|row| serde_json::from_str::<Block>(&row.data).map_err(Into::into))
+                    .collect()
+            },
+            Err(e) => {
+                Err(anyhow::anyhow!("Blocks could not be retrieved: {}", e))
+            }
         }
     }
 
@@ -1,7 +1,6 @@
 use std::sync::Arc;
 use mockall::automock;
-use crate::database::structs::recipient_address::RecipientAddress;
-use crate::mining::mining_reward::MiningReward;
+use crate::chain::block::Block;
 
 pub type DbOperations = Arc<dyn DatabaseOperations + Send + Sync>;
 
@@ -12,9 +11,11 @@ pub trait DatabaseOperations: Send + Sync {
     async fn get_user_balance(&self, user_address: &String) -> anyhow::Result<u64>;
     async fn create_user_if_not_exists(&self, user_address: &String, balance: u64) -> bool;
     async fn update_user_balance(&self, user_address: String, amount: i64) -> bool;
-    async fn save_mining_reward(&self, mining_reward: MiningReward) -> bool;
-    async fn get_mining_reward_at_block_index(&self, block_index: u64) -> anyhow::Result<RecipientAddress>;
-    async fn create_user_and_update_balance(&self, recipient_address: String, amount: i64);
+    async fn set_user_balance(&self, user_address: String, balance: u64) -> bool;
+    async fn save_block(&self, block: &Block) -> bool;
+    async fn delete_blocks_from_index(&self, block_index: u64) -> bool;
+    async fn get_blocks(&self) -> anyhow::Result<Vec<Block>>;
+    fn is_persistent(&self) -> bool;
     async fn drop_database(&self);
     fn get_pool(&self) -> &sqlx::Pool<sqlx::Postgres>;
 }
\ No newline at end of file
@@ -0,0 +1,4 @@
+#[derive(sqlx::FromRow)]
+pub struct BlockData {
+    pub data: String
+}
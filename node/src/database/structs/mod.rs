@@ -1,2 +1,2 @@
 pub mod user_balance;
-pub mod recipient_address;
\ No newline at end of file
+pub mod block_data;
\ No newline at end of file
@@ -1,4 +0,0 @@
-#[derive(sqlx::FromRow, Clone)]
-pub struct RecipientAddress {
-    pub recipient_address: String
-}
\ No newline at end of file
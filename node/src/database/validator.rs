@@ -1,6 +1,9 @@
+use anyhow::{anyhow, Result};
 use crate::database::operations::DbOperations;
+use crate::chain::account_state::AccountState;
 use crate::chain::transaction::Transaction;
 use crate::chain::wallet::Wallet;
+use crate::mempool::Mempool;
 
 pub struct Validator {
     pub db: DbOperations
@@ -13,13 +16,103 @@ impl Validator {
         }
     }
 
-    pub async fn validate_transaction(&self, transaction: &Transaction) -> bool {
+    // Checks a transaction against the chain state and the sender's transactions already in the mempool.
+    // Pending spends are deducted from the balance so the same coins cannot be spent twice.
+    pub fn validate_transaction(&self, transaction: &Transaction, state: &AccountState, mempool: &Mempool) -> Result<()> {
         let sender_address = Wallet::derive_address_hash_from_string(&transaction.sender);
-        match self.db.get_user_balance(&sender_address).await {
-            Ok(user_balance) => {
-                user_balance >= transaction.amount
-            },
-            Err(_) => false
+        let pending = mempool.get_pending_spends(&sender_address);
+
+        if let Some(conflict_id) = pending.and_then(|pending| pending.nonces.get(&transaction.nonce)) {
+            return Err(anyhow!("Conflicts with pending transaction {} using nonce {}", conflict_id, transaction.nonce));
+        }
+
+        let expected_nonce = Self::get_next_nonce(&sender_address, state, mempool);
+        if transaction.nonce != expected_nonce {
+            return Err(anyhow!("Invalid nonce, expected {}", expected_nonce));
+        }
+
+        let total = transaction.amount.checked_add(transaction.fee)
+            .ok_or(anyhow!("Transaction amount overflows"))?;
+        let pending_spend = pending.map(|pending| pending.total).unwrap_or(0);
+        let available = state.get_balance(&sender_address).saturating_sub(pending_spend);
+        if available < total {
+            return Err(anyhow!(
+                "Insufficient funds, {} available after {} pending transactions but {} required",
+                available,
+                pending.map(|pending| pending.nonces.len()).unwrap_or(0),
+                total
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Next nonce for an address, counting its transactions still waiting in the mempool
+    pub fn get_next_nonce(address: &str, state: &AccountState, mempool: &Mempool) -> u64 {
+        let pending_count = mempool.get_pending_spends(address)
+            .map(|pending| pending.nonces.len() as u64)
+            .unwrap_or(0);
+
+        state.get_nonce(address) + pending_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::database::operations::MockDatabaseOperations;
+
+    fn create_transaction(sender: &Wallet, amount: u64, nonce: u64) -> Transaction {
+        let mut transaction = Transaction {
+            sender: sender.get_public_key(),
+            recipient: "recipient".to_string(),
+            amount,
+            fee: 1,
+            timestamp: 0,
+            nonce,
+            id: String::new(),
+            signature: None
+        };
+        transaction.id = transaction.create_id();
+        transaction
+    }
+
+    fn create_mempool(pending: Vec<Transaction>) -> Mempool {
+        let mut mempool = Mempool::default();
+        for transaction in pending {
+            mempool.add(transaction).unwrap();
         }
+        mempool
     }
-}
\ No newline at end of file
+
+    fn create_funded_state(wallet: &Wallet, balance: u64) -> AccountState {
+        let mut state = AccountState::new();
+        state.balances.insert(wallet.address.clone(), balance);
+        state
+    }
+
+    #[test]
+    fn test_pending_spends_reduce_available_balance() {
+        let validator = Validator::new(Arc::new(MockDatabaseOperations::new()));
+        let sender = Wallet::new();
+        let state = create_funded_state(&sender, 100);
+        let pending = create_mempool(vec![create_transaction(&sender, 60, 0)]);
+
+        assert!(validator.validate_transaction(&create_transaction(&sender, 38, 1), &state, &pending).is_ok());
+        let error = validator.validate_transaction(&create_transaction(&sender, 40, 1), &state, &pending).unwrap_err();
+        assert!(error.to_string().starts_with("Insufficient funds"));
+    }
+
+    #[test]
+    fn test_conflicting_nonce_is_rejected() {
+        let validator = Validator::new(Arc::new(MockDatabaseOperations::new()));
+        let sender = Wallet::new();
+        let state = create_funded_state(&sender, 100);
+        let pending_transaction = create_transaction(&sender, 10, 0);
+
+        let error = validator.validate_transaction(&create_transaction(&sender, 20, 0), &state, &create_mempool(vec![pending_transaction.clone()])).unwrap_err();
+
+        assert_eq!(error.to_string(), format!("Conflicts with pending transaction {} using nonce 0", pending_transaction.id));
+    }
+}
@@ -8,6 +8,9 @@ use crate::args::args::Args;
 use crate::args::mode::Mode;
 use crate::args::node_type::NodeType;
 use crate::chain::block::Block;
+use crate::chain::blockchain::Blockchain;
+use crate::chain::chain_spec::ChainSpec;
+use crate::chain::genesis::build_genesis_block;
 use crate::database::connection::Connection;
 use crate::database::operations::DbOperations;
 use crate::database::validator::Validator;
@@ -15,7 +18,10 @@ use crate::mining::mining_tasks::spawn_mining_loop;
 use crate::node::Node;
 use crate::network::tcp_connection::{create_node, start_peer_connection};
 use crate::server::server::start_server;
-use crate::tasks::genesis_tasks::{construct_blockchain, send_genesis_block};
+use crate::tasks::block_sync::sync_chain;
+use crate::tasks::genesis_tasks::send_genesis_block;
+use crate::tasks::mempool_tasks::{restore_mempool, save_mempool, spawn_mempool_expiry_loop};
+use crate::tasks::persistence_tasks::{restore_blockchain, save_chain_update};
 
 extern crate sqlx;
 
@@ -27,7 +33,6 @@ pub async fn test_init(
     let mining_flag = Arc::new(AtomicBool::new(true));
     let validator = Arc::new(Validator::new(db.clone()));
 
-    let miner_address = node.lock().await.wallet.address.clone();
     let peer_address = match args.node_type.get_mode() {
         Mode::OPEN { .. } => None,
         Mode::JOIN { peer_address, .. } => Some(peer_address.clone()),
@@ -40,18 +45,23 @@ pub async fn test_init(
         mining_flag.clone(),
         node.clone(),
         db.clone(),
-        miner_address,
         args
     ).await
 }
 
 pub async fn init() -> Result<()> {
     let args = Args::parse();
+    let spec = match &args.chain_spec {
+        Some(path) => ChainSpec::load(path).map_err(|e| anyhow::anyhow!("Failed to load chain spec {}: {}", path.display(), e))?,
+        None => ChainSpec::default()
+    };
+    let genesis = build_genesis_block(&spec)?;
+    println!("Running on network {} with genesis block {}", spec.network_id, genesis.hash);
+
     let mining_flag = Arc::new(AtomicBool::new(true));
     let db = Arc::new(Connection::new().await);
     let validator = Arc::new(Validator::new(db.clone()));
-    let node = create_node(&args, validator.clone(), mining_flag.clone()).await;
-    let mempool = node.lock().await.mempool.clone();
+    let node = create_node(&args, Blockchain::with_genesis(spec, genesis), validator.clone(), mining_flag.clone()).await;
 
     let wallet = node.lock().await.wallet.clone();
     println!("Wallet private key: {}", wallet.get_private_key());
@@ -65,19 +75,18 @@ pub async fn init() -> Result<()> {
         mining_flag.clone(),
         node.clone(),
         db.clone(),
-        miner_address,
         args.clone()
     ).await?;
 
     match args.node_type {
         NodeType::FULL(_) => {
             tokio::select! {
-                _ = start_server(mempool.clone(), validator.clone()) => {
+                _ = start_server(node.clone(), validator.clone(), mining_flag.clone()) => {
                     println!("Server shutting down...");
                 }
                 _ = tokio::signal::ctrl_c() => {
                     println!("\nCtrl+C received, cleaning up...");
-                    cleanup(db.clone()).await.expect("Cleanup failed");
+                    cleanup(node.clone(), db.clone(), &args).await.expect("Cleanup failed");
                 }
             }
         }
@@ -85,7 +94,7 @@ pub async fn init() -> Result<()> {
             tokio::select! {
                 _ = tokio::signal::ctrl_c() => {
                     println!("\nCtrl+C received, cleaning up...");
-                    cleanup(db.clone()).await.expect("Cleanup failed");
+                    cleanup(node.clone(), db.clone(), &args).await.expect("Cleanup failed");
                 }
             }
         }
@@ -98,15 +107,25 @@ async fn start_blockchain(
     mining_flag: Arc<AtomicBool>,
     node: Arc<Mutex<Node>>,
     db: DbOperations,
-    miner_address: String,
     args: Args
 ) -> Result<()> {
     let is_opening_node = matches!(args.node_type.get_mode(), Mode::OPEN { .. });
+    let blockchain_restored = restore_blockchain(node.clone(), db.clone()).await;
 
+    // Every node derives the same genesis block from the chain spec
     let mut genesis_block: Option<Block> = None;
-    if is_opening_node {
-        println!("Mining genesis block");
-        genesis_block = Some(node.lock().await.blockchain.create_genesis_block(miner_address.clone()));
+    if !blockchain_restored {
+        println!("Starting chain from the chain spec genesis block");
+        let (block, chain_update) = {
+            let mut locked_node = node.lock().await;
+            let block = locked_node.blockchain.create_genesis_block();
+            (block, locked_node.blockchain.get_chain_update(""))
+        };
+        save_chain_update(db.clone(), &chain_update).await;
+
+        if is_opening_node {
+            genesis_block = Some(block);
+        }
     }
 
     loop {
@@ -121,22 +140,49 @@ async fn start_blockchain(
         tokio::time::sleep(Duration::from_secs(5)).await;
     }
 
-    if genesis_block.is_some() {
-        send_genesis_block(node.clone(), &genesis_block.unwrap()).await;
+    if let Some(genesis_block) = genesis_block {
+        send_genesis_block(node.clone(), &genesis_block).await;
     } else {
-        let blockchain_constructed = construct_blockchain(node.clone()).await;
-        if !blockchain_constructed {
-            return Err(anyhow::anyhow!("Failed to construct blockchain from peers."));
-        }
+        println!("Downloading blocks mined since the local chain tip...");
+        sync_chain(node.clone(), db.clone()).await
+            .map_err(|e| anyhow::anyhow!("Initial block download failed, restart to resume: {}", e))?;
     }
 
-    node.lock().await.blockchain_locked = false;
-    spawn_mining_loop(node.clone(), mining_flag.clone(), db.clone());
+    let chain_update = {
+        let mut locked_node = node.lock().await;
+        let previous_tip_hash = locked_node.blockchain.get_tip_hash();
+        locked_node.blockchain.connect_known_orphans();
+        locked_node.blockchain_locked = false;
+        let chain_update = locked_node.blockchain.get_chain_update(&previous_tip_hash);
+        locked_node.reconcile_mempool(&chain_update).await;
+        chain_update
+    };
+    save_chain_update(db.clone(), &chain_update).await;
+
+    if let Some(path) = &args.mempool_file {
+        restore_mempool(node.clone(), path).await;
+    }
+    spawn_mempool_expiry_loop(node.clone());
+    if args.no_mining {
+        println!("Built-in mining disabled... Waiting for external miners");
+    } else {
+        spawn_mining_loop(node.clone(), mining_flag.clone(), db.clone(), args.mining_threads);
+    }
 
     Ok(())
 }
 
-pub async fn cleanup(db: DbOperations) -> Result<()> {
+pub async fn cleanup(node: Arc<Mutex<Node>>, db: DbOperations, args: &Args) -> Result<()> {
+    if let Some(path) = &args.mempool_file {
+        save_mempool(node, path).await;
+    }
+
+    if db.is_persistent() {
+        db.get_pool().close().await;
+        println!("Database kept so the chain can be restored on restart.");
+        return Ok(());
+    }
+
     let pool = db.get_pool().clone();
     drop(pool);
 
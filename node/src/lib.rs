@@ -6,5 +6,6 @@ mod constants;
 pub mod init;
 mod tasks;
 pub mod node;
+pub mod mempool;
 pub mod chain;
-mod mining;
\ No newline at end of file
+pub mod mining;
\ No newline at end of file
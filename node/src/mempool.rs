@@ -0,0 +1,639 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::chain::account_state::AccountState;
+use crate::chain::chain_update::ChainUpdate;
+use crate::chain::transaction::Transaction;
+use crate::chain::wallet::Wallet;
+use crate::constants::{MAX_MEMPOOL_TRANSACTIONS, MAX_SEEN_TRANSACTIONS, MEMPOOL_EXPIRY};
+
+/*
+    Transactions waiting to be mined, prioritised by fee rate.
+    The fee rate is the fee paid per byte of the serialized transaction.
+    When the pool is full a new transaction must pay a higher fee rate
+    than the cheapest pending transaction, which is then evicted.
+    The ids of recently seen transactions are remembered so that
+    transactions gossiped between peers are only relayed once.
+    Transactions that stay pending for longer than the expiry are dropped.
+    Pending spends and nonces are indexed by sender address so validating
+    a new transaction does not scan the whole pool.
+*/
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MempoolEntry {
+    pub transaction: Transaction,
+    pub added_at: i64,
+    // Serialized size, computed once so fee rate comparisons do not re-serialize the transaction
+    #[serde(default)]
+    pub size: usize,
+    #[serde(default)]
+    pub sender_address: String
+}
+
+impl MempoolEntry {
+    pub fn new(transaction: Transaction, added_at: i64) -> Result<Self> {
+        let size = transaction.get_size();
+        let sender_address = Wallet::try_load_from_public_key(&transaction.sender)
+            .ok_or(anyhow!("Transaction sender is not a valid public key"))?
+            .address;
+        Ok(Self { transaction, added_at, size, sender_address })
+    }
+
+    fn get_total(&self) -> u64 {
+        self.transaction.amount.saturating_add(self.transaction.fee)
+    }
+}
+
+// Transactions from one sender waiting in the mempool
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PendingSpends {
+    // Amount plus fee of every pending transaction
+    pub total: u64,
+    // Id of the pending transaction using each nonce
+    pub nonces: HashMap<u64, String>
+}
+
+pub struct Mempool {
+    transactions: HashMap<String, MempoolEntry>,
+    senders: HashMap<String, PendingSpends>,
+    max_transactions: usize,
+    expiry: i64,
+    seen: HashSet<String>,
+    seen_order: VecDeque<String>
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new(MAX_MEMPOOL_TRANSACTIONS, MEMPOOL_EXPIRY)
+    }
+}
+
+impl Mempool {
+    pub fn new(max_transactions: usize, expiry: i64) -> Self {
+        Self {
+            transactions: HashMap::new(),
+            senders: HashMap::new(),
+            max_transactions,
+            expiry,
+            seen: HashSet::new(),
+            seen_order: VecDeque::new()
+        }
+    }
+
+    pub fn add(&mut self, transaction: Transaction) -> Result<()> {
+        self.add_entry(MempoolEntry::new(transaction, Utc::now().timestamp())?)
+    }
+
+    fn add_entry(&mut self, entry: MempoolEntry) -> Result<()> {
+        if self.transactions.contains_key(&entry.transaction.id) {
+            return Err(anyhow!("Transaction already in mempool"));
+        }
+
+        // Keeps the sender index one entry per nonce, a transaction returned from a disconnected block
+        // is dropped when a pending transaction already uses its nonce
+        if let Some(conflict_id) = self.senders.get(&entry.sender_address)
+            .and_then(|spends| spends.nonces.get(&entry.transaction.nonce)) {
+            return Err(anyhow!("Conflicts with pending transaction {} using nonce {}", conflict_id, entry.transaction.nonce));
+        }
+
+        if self.transactions.len() >= self.max_transactions {
+            let cheapest = self.transactions.values()
+                .min_by(|a, b| compare_fee_rate(a, b))
+                .cloned()
+                .ok_or(anyhow!("Mempool has no capacity"))?;
+
+            if compare_fee_rate(&entry, &cheapest) != Ordering::Greater {
+                return Err(anyhow!("Mempool is full, fee rate too low"));
+            }
+
+            println!("Mempool full... Evicting transaction {}", cheapest.transaction.id);
+            self.remove_entry(&cheapest.transaction.id);
+        }
+
+        let spends = self.senders.entry(entry.sender_address.clone()).or_default();
+        spends.total = spends.total.saturating_add(entry.get_total());
+        spends.nonces.insert(entry.transaction.nonce, entry.transaction.id.clone());
+        self.transactions.insert(entry.transaction.id.clone(), entry);
+        Ok(())
+    }
+
+    fn remove_entry(&mut self, transaction_id: &str) -> Option<MempoolEntry> {
+        let entry = self.transactions.remove(transaction_id)?;
+        if let Some(spends) = self.senders.get_mut(&entry.sender_address) {
+            spends.total = spends.total.saturating_sub(entry.get_total());
+            spends.nonces.remove(&entry.transaction.nonce);
+            if spends.nonces.is_empty() {
+                self.senders.remove(&entry.sender_address);
+            }
+        }
+
+        Some(entry)
+    }
+
+    fn retain(&mut self, keep: impl Fn(&MempoolEntry) -> bool) -> usize {
+        let removed: Vec<String> = self.transactions.values()
+            .filter(|entry| !keep(entry))
+            .map(|entry| entry.transaction.id.clone())
+            .collect();
+        for transaction_id in &removed {
+            self.remove_entry(transaction_id);
+        }
+
+        removed.len()
+    }
+
+    // Returns false if the transaction was already seen
+    pub fn mark_seen(&mut self, transaction_id: &str) -> bool {
+        if !self.seen.insert(transaction_id.to_string()) {
+            return false;
+        }
+
+        self.seen_order.push_back(transaction_id.to_string());
+        if self.seen_order.len() > MAX_SEEN_TRANSACTIONS
+            && let Some(oldest) = self.seen_order.pop_front() {
+            self.seen.remove(&oldest);
+        }
+
+        true
+    }
+
+    pub fn has_seen(&self, transaction_id: &str) -> bool {
+        self.seen.contains(transaction_id)
+    }
+
+    pub fn remove_transactions(&mut self, transactions: &[Transaction]) {
+        for transaction in transactions {
+            self.remove_entry(&transaction.id);
+        }
+    }
+
+    // Brings the pool in line with the main chain after blocks were connected or disconnected.
+    // Transactions from disconnected blocks are returned unless the new branch confirmed them,
+    // then the pending transactions of every account those blocks touched are re-validated.
+    pub fn reconcile(&mut self, update: &ChainUpdate, state: &AccountState) {
+        let confirmed: Vec<Transaction> = update.connected
+            .iter()
+            .flat_map(|block| block.transactions.iter().cloned())
+            .collect();
+        self.remove_transactions(&confirmed);
+
+        for block in &update.disconnected {
+            for transaction in &block.transactions {
+                if transaction.is_coinbase() || confirmed.iter().any(|confirmed| confirmed.id == transaction.id) {
+                    continue;
+                }
+
+                if let Err(e) = self.add(transaction.clone()) {
+                    println!("Could not return transaction {} to mempool: {}", transaction.id, e);
+                }
+            }
+        }
+
+        let mut touched: HashSet<String> = update.balances.keys().cloned().collect();
+        touched.extend(update.disconnected
+            .iter()
+            .chain(update.connected.iter())
+            .flat_map(|block| block.transactions.iter())
+            .filter(|transaction| !transaction.is_coinbase())
+            .filter_map(|transaction| Wallet::try_load_from_public_key(&transaction.sender))
+            .map(|sender| sender.address));
+        self.retain_valid(state, &touched);
+    }
+
+    // Drops the transactions of the given senders that can no longer be mined on top of the given state.
+    // A sender's transactions must continue from its confirmed nonce and be covered by its confirmed balance.
+    fn retain_valid<'a>(&mut self, state: &AccountState, senders: impl IntoIterator<Item = &'a String>) {
+        let mut invalid: Vec<String> = vec![];
+        for sender in senders {
+            let Some(pending) = self.senders.get(sender) else {
+                continue;
+            };
+
+            let mut next_nonce = state.get_nonce(sender);
+            let mut balance = state.get_balance(sender);
+            let mut nonces: Vec<&u64> = pending.nonces.keys().collect();
+            nonces.sort();
+            for nonce in nonces {
+                let transaction_id = &pending.nonces[nonce];
+                let total = self.transactions[transaction_id].get_total();
+                if *nonce == next_nonce && total <= balance {
+                    next_nonce += 1;
+                    balance -= total;
+                } else {
+                    invalid.push(transaction_id.clone());
+                }
+            }
+        }
+
+        for transaction_id in &invalid {
+            self.remove_entry(transaction_id);
+        }
+
+        let invalid_count = invalid.len();
+        if invalid_count > 0 {
+            println!("Dropped {} mempool transactions no longer valid on the new chain", invalid_count);
+        }
+    }
+
+    // Removes transactions pending for longer than the expiry, returning how many were dropped
+    pub fn remove_expired(&mut self, now: i64) -> usize {
+        let expiry = self.expiry;
+
+        self.retain(|entry| now - entry.added_at < expiry)
+    }
+
+    pub fn purge(&mut self) -> usize {
+        let count = self.transactions.len();
+        self.transactions.clear();
+        self.senders.clear();
+
+        count
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string(&self.get_entries())?;
+        fs::write(path, json)?;
+
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<Vec<MempoolEntry>> {
+        let json = fs::read_to_string(path)?;
+
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    // Re-adds saved entries that have not expired and are still valid on the given state.
+    // Entries keep their original insertion time so a restart does not extend their expiry.
+    // The file is not trusted, entries without a valid sender signature are skipped.
+    pub fn restore(&mut self, entries: Vec<MempoolEntry>, state: &AccountState, now: i64) -> usize {
+        for entry in entries {
+            if now - entry.added_at >= self.expiry {
+                continue;
+            }
+
+            let is_signed = Wallet::try_load_from_public_key(&entry.transaction.sender)
+                .is_some_and(|sender| sender.verify_signature(&entry.transaction));
+            if !is_signed {
+                println!("Skipping saved transaction {} without a valid signature", entry.transaction.id);
+                continue;
+            }
+
+            self.mark_seen(&entry.transaction.id);
+            let restored = MempoolEntry::new(entry.transaction, entry.added_at).and_then(|entry| self.add_entry(entry));
+            if let Err(e) = restored {
+                println!("Could not restore transaction to mempool: {}", e);
+            }
+        }
+        let senders: Vec<String> = self.senders.keys().cloned().collect();
+        self.retain_valid(state, &senders);
+
+        self.transactions.len()
+    }
+
+    pub fn get(&self, transaction_id: &str) -> Option<&Transaction> {
+        self.transactions.get(transaction_id).map(|entry| &entry.transaction)
+    }
+
+    pub fn get_entry(&self, transaction_id: &str) -> Option<&MempoolEntry> {
+        self.transactions.get(transaction_id)
+    }
+
+    pub fn get_pending_spends(&self, sender_address: &str) -> Option<&PendingSpends> {
+        self.senders.get(sender_address)
+    }
+
+    pub fn contains(&self, transaction_id: &str) -> bool {
+        self.transactions.contains_key(transaction_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    pub fn get_max_transactions(&self) -> usize {
+        self.max_transactions
+    }
+
+    pub fn get_expiry(&self) -> i64 {
+        self.expiry
+    }
+
+    // Pending transactions ordered from the highest to the lowest fee rate
+    pub fn get_transactions(&self) -> Vec<Transaction> {
+        self.get_entries().into_iter().map(|entry| entry.transaction).collect()
+    }
+
+    pub fn get_entries(&self) -> Vec<MempoolEntry> {
+        let mut entries: Vec<MempoolEntry> = self.transactions.values().cloned().collect();
+        entries.sort_by(|a, b| compare_fee_rate(b, a)
+            .then_with(|| a.transaction.nonce.cmp(&b.transaction.nonce)));
+
+        entries
+    }
+
+    // Picks the best paying transactions that apply on top of the given state and fit in a block.
+    // Transactions waiting on a lower nonce from the same sender are retried once it is included.
+    pub fn select_transactions(&self, state: &AccountState, max_transactions: usize, max_size: usize) -> Vec<Transaction> {
+        let mut state = state.clone();
+        let mut remaining = self.get_entries();
+        let mut selected: Vec<Transaction> = vec![];
+        let mut size = 0;
+
+        loop {
+            let mut skipped: Vec<MempoolEntry> = vec![];
+            let selected_count = selected.len();
+
+            for entry in remaining {
+                if selected.len() >= max_transactions || size + entry.size > max_size {
+                    continue;
+                }
+
+                if state.apply_pending_transaction(&entry.transaction, &entry.sender_address).is_ok() {
+                    size += entry.size;
+                    selected.push(entry.transaction);
+                } else {
+                    skipped.push(entry);
+                }
+            }
+
+            if selected.len() == selected_count || skipped.is_empty() {
+                break;
+            }
+            remaining = skipped;
+        }
+
+        selected
+    }
+}
+
+// Compares fee per byte without floating point by cross multiplying
+fn compare_fee_rate(a: &MempoolEntry, b: &MempoolEntry) -> Ordering {
+    let a_rate = a.transaction.fee as u128 * b.size as u128;
+    let b_rate = b.transaction.fee as u128 * a.size as u128;
+
+    a_rate.cmp(&b_rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::block::Block;
+    use crate::chain::wallet::Wallet;
+
+    fn create_transaction(sender: &Wallet, fee: u64, nonce: u64) -> Transaction {
+        let mut transaction = Transaction {
+            sender: sender.get_public_key(),
+            recipient: "recipient".to_string(),
+            amount: 10,
+            fee,
+            timestamp: 0,
+            nonce,
+            id: String::new(),
+            signature: None
+        };
+        transaction.id = transaction.create_id();
+        sender.create_signature(&mut transaction);
+        transaction
+    }
+
+    fn create_funded_state(wallets: &[&Wallet]) -> AccountState {
+        let mut state = AccountState::new();
+        for wallet in wallets {
+            let coinbase = Transaction::new_coinbase(wallet.address.clone(), 100, 0);
+            state.apply_block(&Block::new(1, "previousBlockHash".to_string(), vec![], "minerAddress".to_string()), Some(&coinbase)).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn test_transactions_ordered_by_fee_rate() {
+        let sender = Wallet::new();
+        let mut mempool = Mempool::new(10, MEMPOOL_EXPIRY);
+        mempool.add(create_transaction(&sender, 1, 0)).unwrap();
+        mempool.add(create_transaction(&sender, 5, 1)).unwrap();
+        mempool.add(create_transaction(&sender, 3, 2)).unwrap();
+
+        let fees: Vec<u64> = mempool.get_transactions().iter().map(|transaction| transaction.fee).collect();
+
+        assert_eq!(fees, vec![5, 3, 1]);
+    }
+
+    #[test]
+    fn test_full_mempool_evicts_lowest_fee() {
+        let sender = Wallet::new();
+        let mut mempool = Mempool::new(2, MEMPOOL_EXPIRY);
+        let cheapest = create_transaction(&sender, 1, 0);
+        mempool.add(cheapest.clone()).unwrap();
+        mempool.add(create_transaction(&sender, 2, 1)).unwrap();
+
+        assert!(mempool.add(create_transaction(&sender, 0, 2)).is_err());
+        assert!(mempool.add(create_transaction(&sender, 3, 3)).is_ok());
+        assert_eq!(mempool.len(), 2);
+        assert!(!mempool.contains(&cheapest.id));
+    }
+
+    #[test]
+    fn test_mempool_without_capacity_rejects_transactions() {
+        let mut mempool = Mempool::new(0, MEMPOOL_EXPIRY);
+
+        assert!(mempool.add(create_transaction(&Wallet::new(), 1, 0)).is_err());
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_select_transactions_respects_nonce_order() {
+        let sender = Wallet::new();
+        let state = create_funded_state(&[&sender]);
+        let mut mempool = Mempool::new(10, MEMPOOL_EXPIRY);
+        mempool.add(create_transaction(&sender, 1, 0)).unwrap();
+        mempool.add(create_transaction(&sender, 9, 1)).unwrap();
+
+        let nonces: Vec<u64> = mempool.select_transactions(&state, 10, usize::MAX).iter().map(|transaction| transaction.nonce).collect();
+
+        assert_eq!(nonces, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_select_transactions_prefers_higher_fees_when_full() {
+        let low_payer = Wallet::new();
+        let high_payer = Wallet::new();
+        let state = create_funded_state(&[&low_payer, &high_payer]);
+        let mut mempool = Mempool::new(10, MEMPOOL_EXPIRY);
+        mempool.add(create_transaction(&low_payer, 1, 0)).unwrap();
+        let high_fee = create_transaction(&high_payer, 8, 0);
+        mempool.add(high_fee.clone()).unwrap();
+
+        assert_eq!(mempool.select_transactions(&state, 1, usize::MAX), vec![high_fee.clone()]);
+        assert_eq!(mempool.select_transactions(&state, 10, high_fee.get_size()), vec![high_fee]);
+    }
+
+    #[test]
+    fn test_reconcile_after_reorg() {
+        let sender = Wallet::new();
+        let other_sender = Wallet::new();
+        let state = create_funded_state(&[&sender, &other_sender]);
+        let returned = create_transaction(&sender, 1, 0);
+        let confirmed = create_transaction(&other_sender, 2, 0);
+        let stale = create_transaction(&other_sender, 3, 5);
+        let disconnected_block = Block::new(2, "forkPoint".to_string(), vec![Transaction::new_coinbase("minerAddress".to_string(), 50, 2), returned.clone()], "minerAddress".to_string());
+        let connected_block = Block::new(2, "forkPoint".to_string(), vec![confirmed.clone()], "minerAddress".to_string());
+        let mut mempool = Mempool::new(10, MEMPOOL_EXPIRY);
+        mempool.add(confirmed.clone()).unwrap();
+        mempool.add(stale.clone()).unwrap();
+
+        let update = ChainUpdate {
+            disconnected: vec![disconnected_block],
+            connected: vec![connected_block],
+            ..Default::default()
+        };
+        mempool.reconcile(&update, &state);
+
+        assert!(mempool.contains(&returned.id));
+        assert!(!mempool.contains(&confirmed.id));
+        assert!(!mempool.contains(&stale.id));
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn test_reconcile_drops_transactions_the_sender_can_no_longer_afford() {
+        let sender = Wallet::new();
+        let mut state = create_funded_state(&[&sender]);
+        let mut spend = create_transaction(&sender, 5, 0);
+        spend.amount = 80;
+        let connected_block = Block::new(2, "forkPoint".to_string(), vec![spend], "minerAddress".to_string());
+        state.apply_block(&connected_block, None).unwrap();
+        let affordable = create_transaction(&sender, 1, 1);
+        let unaffordable = create_transaction(&sender, 1, 2);
+        let mut mempool = Mempool::new(10, MEMPOOL_EXPIRY);
+        mempool.add(affordable.clone()).unwrap();
+        mempool.add(unaffordable.clone()).unwrap();
+
+        let update = ChainUpdate {
+            connected: vec![connected_block],
+            balances: HashMap::from([(sender.address.clone(), state.get_balance(&sender.address))]),
+            ..Default::default()
+        };
+        mempool.reconcile(&update, &state);
+
+        assert!(mempool.contains(&affordable.id));
+        assert!(!mempool.contains(&unaffordable.id));
+        assert_eq!(mempool.get_pending_spends(&sender.address).unwrap().total, 11);
+    }
+
+    #[test]
+    fn test_expired_transactions_are_removed() {
+        let sender = Wallet::new();
+        let mut mempool = Mempool::new(10, 60);
+        let transaction = create_transaction(&sender, 1, 0);
+        mempool.add_entry(MempoolEntry::new(transaction.clone(), 1000).unwrap()).unwrap();
+
+        assert_eq!(mempool.remove_expired(1059), 0);
+        assert_eq!(mempool.remove_expired(1060), 1);
+        assert!(!mempool.contains(&transaction.id));
+    }
+
+    #[test]
+    fn test_restore_from_file() {
+        let sender = Wallet::new();
+        let state = create_funded_state(&[&sender]);
+        let path = std::env::temp_dir().join(format!("mempool_{}.json", uuid::Uuid::new_v4()));
+        let mut mempool = Mempool::new(10, 60);
+        let kept = create_transaction(&sender, 1, 0);
+        let expired = create_transaction(&sender, 2, 1);
+        mempool.add_entry(MempoolEntry::new(kept.clone(), 1000).unwrap()).unwrap();
+        mempool.add_entry(MempoolEntry::new(expired.clone(), 900).unwrap()).unwrap();
+        mempool.save_to_file(&path).unwrap();
+
+        let mut restored = Mempool::new(10, 60);
+        let count = restored.restore(Mempool::load_from_file(&path).unwrap(), &state, 1030);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(count, 1);
+        assert_eq!(restored.get_entries(), vec![MempoolEntry::new(kept, 1000).unwrap()]);
+    }
+
+    #[test]
+    fn test_restore_skips_invalid_entries() {
+        let sender = Wallet::new();
+        let state = create_funded_state(&[&sender]);
+        let valid = create_transaction(&sender, 1, 0);
+        let mut unsigned = create_transaction(&sender, 2, 1);
+        unsigned.signature = None;
+        let mut malformed_sender = create_transaction(&sender, 3, 2);
+        malformed_sender.sender = "not a public key".to_string();
+        let entries = [valid.clone(), unsigned, malformed_sender]
+            .into_iter()
+            .map(|transaction| MempoolEntry { transaction, added_at: 1000, size: 0, sender_address: String::new() })
+            .collect();
+
+        let mut restored = Mempool::new(10, 60);
+        assert_eq!(restored.restore(entries, &state, 1030), 1);
+        assert!(restored.contains(&valid.id));
+    }
+
+    #[test]
+    fn test_entry_requires_valid_sender() {
+        let mut transaction = create_transaction(&Wallet::new(), 1, 0);
+        transaction.sender = "not a public key".to_string();
+
+        assert!(MempoolEntry::new(transaction.clone(), 1000).is_err());
+        assert!(Mempool::default().add(transaction).is_err());
+    }
+
+    #[test]
+    fn test_pending_spends_follow_the_pool() {
+        let sender = Wallet::new();
+        let mut mempool = Mempool::new(10, MEMPOOL_EXPIRY);
+        let first = create_transaction(&sender, 1, 0);
+        mempool.add(first.clone()).unwrap();
+        mempool.add(create_transaction(&sender, 2, 1)).unwrap();
+
+        let spends = mempool.get_pending_spends(&sender.address).unwrap();
+        assert_eq!(spends.total, 10 + 1 + 10 + 2);
+        assert_eq!(spends.nonces[&0], first.id);
+
+        mempool.remove_transactions(&[first]);
+        assert_eq!(mempool.get_pending_spends(&sender.address).unwrap().total, 10 + 2);
+
+        mempool.purge();
+        assert!(mempool.get_pending_spends(&sender.address).is_none());
+    }
+
+    #[test]
+    fn test_same_sender_and_nonce_is_rejected() {
+        let sender = Wallet::new();
+        let mut mempool = Mempool::new(10, MEMPOOL_EXPIRY);
+        let pending = create_transaction(&sender, 1, 0);
+        let conflicting = create_transaction(&sender, 2, 0);
+        let next = create_transaction(&sender, 1, 1);
+        mempool.add(pending.clone()).unwrap();
+        mempool.add(next).unwrap();
+
+        assert!(mempool.add(conflicting.clone()).is_err());
+        assert!(!mempool.contains(&conflicting.id));
+
+        mempool.remove_transactions(&[pending]);
+        let spends = mempool.get_pending_spends(&sender.address).unwrap();
+        assert_eq!(spends.total, 10 + 1);
+        assert_eq!(spends.nonces.len(), 1);
+    }
+
+    #[test]
+    fn test_mark_seen_deduplicates() {
+        let mut mempool = Mempool::new(10, MEMPOOL_EXPIRY);
+
+        assert!(!mempool.has_seen("transaction"));
+        assert!(mempool.mark_seen("transaction"));
+        assert!(!mempool.mark_seen("transaction"));
+        assert!(mempool.has_seen("transaction"));
+    }
+}
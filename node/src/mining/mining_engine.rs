@@ -0,0 +1,98 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::chain::block::Block;
+use crate::constants::{HASHRATE_REPORT_INTERVAL, MINING_BATCH_SIZE};
+
+/*
+    Searches for a valid nonce on several threads.
+    Worker i starts at nonce i and advances by the number of workers,
+    so every nonce is tried by exactly one worker.
+    All workers stop when one finds a block or the cancel flag is cleared,
+    and the combined hashrate is reported while they run.
+*/
+
+pub fn mine_parallel(block: Block, threads: usize, cancel_flag: Arc<AtomicBool>) -> Option<Block> {
+    let threads = threads.max(1);
+    let found = AtomicBool::new(false);
+    let hashes = AtomicU64::new(0);
+    let started_at = Instant::now();
+    let (sender, receiver) = mpsc::channel::<Block>();
+
+    let mined_block = thread::scope(|scope| {
+        for worker in 0..threads {
+            let mut worker_block = block.clone();
+            worker_block.nonce = block.nonce.wrapping_add(worker as u64);
+            let sender = sender.clone();
+            let (found, hashes, cancel_flag) = (&found, &hashes, &cancel_flag);
+
+            scope.spawn(move || {
+                let step = threads as u64;
+                while cancel_flag.load(Ordering::Relaxed) && !found.load(Ordering::Relaxed) {
+                    // Hashes are added per batch so workers do not contend on the shared counter for every hash
+                    let start_nonce = worker_block.nonce;
+                    let mined = worker_block.mine_with_step(step, MINING_BATCH_SIZE);
+                    hashes.fetch_add(worker_block.nonce.wrapping_sub(start_nonce) / step + mined as u64, Ordering::Relaxed);
+
+                    if mined {
+                        found.store(true, Ordering::Relaxed);
+                        let _ = sender.send(worker_block);
+                        return;
+                    }
+                }
+            });
+        }
+        // Only the workers hold senders, so the channel closes once they have all stopped
+        drop(sender);
+
+        loop {
+            match receiver.recv_timeout(Duration::from_secs(HASHRATE_REPORT_INTERVAL)) {
+                Ok(mined_block) => return Some(mined_block),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    println!("Mining block {} on {} threads at {:.0} H/s", block.index, threads, get_hashrate(&hashes, started_at));
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => return None
+            }
+        }
+    });
+
+    if mined_block.is_some() {
+        println!("Found block {} after {} hashes at {:.0} H/s", block.index, hashes.load(Ordering::Relaxed), get_hashrate(&hashes, started_at));
+    }
+
+    mined_block
+}
+
+fn get_hashrate(hashes: &AtomicU64, started_at: Instant) -> f64 {
+    let elapsed = started_at.elapsed().as_secs_f64();
+    if elapsed == 0.0 {
+        return 0.0;
+    }
+
+    hashes.load(Ordering::Relaxed) as f64 / elapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mine_parallel_finds_valid_block() {
+        let block = Block::new(1, "previousBlockHash".to_string(), vec![], "minerAddress".to_string());
+        let cancel_flag = Arc::new(AtomicBool::new(true));
+
+        let mined_block = mine_parallel(block, 4, cancel_flag).expect("Block should be mined");
+
+        assert!(mined_block.has_valid_proof_of_work());
+    }
+
+    #[test]
+    fn test_mine_parallel_stops_when_cancelled() {
+        let block = Block::new(1, "previousBlockHash".to_string(), vec![], "minerAddress".to_string());
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+
+        assert!(mine_parallel(block, 4, cancel_flag).is_none());
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+use crate::chain::block::Block;
+use crate::constants::MAX_MINING_JOBS;
+
+/*
+    Block templates handed out to external miners.
+    A miner only returns the job id and the nonce it found,
+    so the node keeps each template until it is solved or goes stale.
+    Jobs built on an older tip are dropped when a job for a new tip is created.
+*/
+
+#[derive(Default)]
+pub struct MiningJobs {
+    jobs: HashMap<String, Block>,
+    order: VecDeque<String>
+}
+
+impl MiningJobs {
+    pub fn add(&mut self, block: Block) -> String {
+        self.jobs.retain(|_, job| job.previous_block_hash == block.previous_block_hash);
+        self.order.retain(|job_id| self.jobs.contains_key(job_id));
+
+        if self.order.len() >= MAX_MINING_JOBS
+            && let Some(oldest) = self.order.pop_front() {
+            self.jobs.remove(&oldest);
+        }
+
+        let job_id = Uuid::new_v4().to_string();
+        self.jobs.insert(job_id.clone(), block);
+        self.order.push_back(job_id.clone());
+
+        job_id
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<&Block> {
+        self.jobs.get(job_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jobs_for_old_tip_are_dropped() {
+        let mut jobs = MiningJobs::default();
+        let old_job = jobs.add(Block::new(1, "oldTip".to_string(), vec![], "minerAddress".to_string()));
+        let other_old_job = jobs.add(Block::new(1, "oldTip".to_string(), vec![], "minerAddress".to_string()));
+        assert_eq!(jobs.len(), 2);
+
+        let new_job = jobs.add(Block::new(2, "newTip".to_string(), vec![], "minerAddress".to_string()));
+
+        assert!(jobs.get(&old_job).is_none());
+        assert!(jobs.get(&other_old_job).is_none());
+        assert!(jobs.get(&new_job).is_some());
+    }
+}
@@ -1,20 +1,25 @@
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use anyhow::{anyhow, Result};
 use tokio::sync::Mutex;
 use crate::chain::block::Block;
-use crate::constants::{MINING_REWARD_AMOUNT, MINING_REWARD_DELAY};
+use crate::chain::block_validation_type::BlockValidationType;
+use crate::chain::blockchain::Blockchain;
+use crate::chain::emission::block_subsidy;
+use crate::constants::{MAX_BLOCK_TRANSACTIONS, MAX_BLOCK_TRANSACTIONS_SIZE};
 use crate::database::operations::DbOperations;
-use crate::mining::mining_reward::MiningReward;
+use crate::mining::mining_engine::mine_parallel;
 use crate::network::message::Message;
 use crate::network::message_sender::broadcast_message;
 use crate::node::Node;
 use crate::chain::transaction::Transaction;
-use crate::chain::wallet::Wallet;
+use crate::tasks::persistence_tasks::save_chain_update;
 
 pub fn spawn_mining_loop(
     node: Arc<Mutex<Node>>,
     mining_flag: Arc<AtomicBool>,
-    db: DbOperations
+    db: DbOperations,
+    mining_threads: usize
 ) {
     tokio::spawn(async move {
         loop {
@@ -22,89 +27,169 @@ pub fn spawn_mining_loop(
                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                 continue;
             }
-            let mined_block: Option<Block> = spawn_mining(node.clone(), mining_flag.clone()).await;
+            let mined_block: Option<Block> = spawn_mining(node.clone(), mining_flag.clone(), mining_threads).await;
 
             if let Some(block) = mined_block {
-                node.lock().await.blockchain.add_block_without_validation(block.clone());
-                node.lock().await.delete_txs_from_mempool(&block.transactions).await;
-
-                let node_address = node.lock().await.wallet.address.clone();
-                save_mining_reward(db.clone(), node_address, block.index).await;
-
-                let transactions = block.transactions.clone();
-                let mined_block_message = Message::BlockMined {
-                    from: node.lock().await.address.clone(),
-                    block
-                };
-                broadcast_message(node.clone(), &mined_block_message).await;
-
-                spawn_update_balances(db.clone(), transactions);
+                submit_mined_block(node.clone(), db.clone(), block).await;
             } else {
-                if node.lock().await.blockchain.invalid_blocks.len() < 5 {
-                    mining_flag.store(true, Ordering::Relaxed);
-                }
+                mining_flag.store(true, Ordering::Relaxed);
             }
         }
     });
 }
 
-async fn spawn_mining(node: Arc<Mutex<Node>>, mining_flag: Arc<AtomicBool>) -> Option<Block> {
-    tokio::task::spawn_blocking({
-        let cancel_flag = mining_flag.clone();
-        let node_inner = node.clone();
-        let transactions = node_inner.lock().await.mempool.lock().await.clone();
-        let blockchain_clone = node_inner.lock().await.blockchain.clone();
-        let node_address = node_inner.lock().await.wallet.address.clone();
-        move || mine_block(
-            transactions,
-            blockchain_clone.get_latest_block().clone().hash,
-            blockchain_clone.get_length() as u64,
-            cancel_flag,
-            node_address
-        )
-    }).await.unwrap()
+// Builds the next block on top of the current tip from the best paying mempool transactions
+pub async fn create_block_template(node: Arc<Mutex<Node>>, miner_address: String) -> Block {
+    let locked_node = node.lock().await;
+    let blockchain = &locked_node.blockchain;
+    // One transaction slot is kept for the coinbase
+    let transactions = locked_node.mempool.lock().await
+        .select_transactions(&blockchain.state, MAX_BLOCK_TRANSACTIONS - 1, MAX_BLOCK_TRANSACTIONS_SIZE);
+
+    build_block(blockchain, miner_address, transactions)
 }
 
-fn mine_block(
-    transactions: Vec<Transaction>,
-    previous_hash: String,
-    block_index: u64,
-    cancel_flag: Arc<AtomicBool>,
-    node_address: String
-) -> Option<Block> {
-    let mut block = Block::new(block_index, previous_hash, transactions, node_address);
-
-    while cancel_flag.load(Ordering::Relaxed) == true {
-        if block.mine() {
-            println!("Mined block {}", block.index);
-            return Some(block);
+// Builds the next block from the given mempool transactions, kept in the order they are listed
+pub async fn create_block_template_from(node: Arc<Mutex<Node>>, miner_address: String, transaction_ids: &[String]) -> Result<Block> {
+    if transaction_ids.len() > MAX_BLOCK_TRANSACTIONS - 1 {
+        return Err(anyhow!("Too many transactions, at most {} fit in a block", MAX_BLOCK_TRANSACTIONS - 1));
+    }
+
+    let locked_node = node.lock().await;
+    let blockchain = &locked_node.blockchain;
+    let mempool = locked_node.mempool.lock().await;
+    let mut state = blockchain.state.clone();
+    let mut transactions: Vec<Transaction> = vec![];
+    let mut size = 0;
+
+    for transaction_id in transaction_ids {
+        let entry = mempool.get_entry(transaction_id)
+            .ok_or(anyhow!("Transaction {} is not in the mempool", transaction_id))?;
+        state.apply_pending_transaction(&entry.transaction, &entry.sender_address)
+            .map_err(|e| anyhow!("Transaction {} cannot be included: {}", transaction_id, e))?;
+
+        size += entry.size;
+        if size > MAX_BLOCK_TRANSACTIONS_SIZE {
+            return Err(anyhow!("Transactions exceed the maximum block size"));
         }
+        transactions.push(entry.transaction.clone());
     }
 
-    None
+    Ok(build_block(blockchain, miner_address, transactions))
 }
 
-async fn save_mining_reward(db: DbOperations, node_address: String, block_index: u64) {
-    let mining_reward = MiningReward::new(
-        MINING_REWARD_AMOUNT,
-        node_address,
-        block_index + MINING_REWARD_DELAY
-    );
-    db.save_mining_reward(mining_reward).await;
+fn build_block(blockchain: &Blockchain, miner_address: String, transactions: Vec<Transaction>) -> Block {
+    let block_index = blockchain.get_length() as u64;
+
+    // The coinbase pays the block reward and fees to the miner and must come first
+    let fees: u64 = transactions.iter().map(|transaction| transaction.fee).sum();
+    let mut block_transactions = vec![Transaction::new_coinbase(miner_address.clone(), block_subsidy(&blockchain.spec, block_index) + fees, block_index)];
+    block_transactions.extend(transactions);
+
+    let mut block = Block::new(block_index, blockchain.get_tip_hash(), block_transactions, miner_address);
+    block.difficulty = blockchain.get_next_difficulty();
+
+    block
 }
 
-pub fn spawn_update_balances(db: DbOperations, transactions: Vec<Transaction>) {
-    tokio::spawn(async move {
-        for transaction in &transactions {
-            // Decrement sender balance
-            let sender_address = Wallet::derive_address_hash_from_string(&transaction.sender);
-            db.create_user_and_update_balance(sender_address.clone(), -(transaction.amount as i64)).await;
-            println!("Updated balance for {}", sender_address);
-
-            // Increment recipient balance
-            let receiver_address = transaction.recipient.clone();
-            db.create_user_and_update_balance(receiver_address.clone(), transaction.amount as i64).await;
-            println!("Updated balance for {}", receiver_address);
-        }
-    });
-}
\ No newline at end of file
+// Connects a block mined by this node or an external miner and announces it to peers
+pub async fn submit_mined_block(node: Arc<Mutex<Node>>, db: DbOperations, block: Block) -> BlockValidationType {
+    let (block_validation_type, chain_update) = {
+        let mut locked_node = node.lock().await;
+        let result = locked_node.receive_block(&block);
+        locked_node.reconcile_mempool(&result.1).await;
+        result
+    };
+    // A block that connects stored orphans into a heavier branch has already switched the chain
+    if !matches!(block_validation_type, BlockValidationType::Valid | BlockValidationType::Reorg) {
+        println!("Mined block {} was not added to the main chain: {}", block.index, block_validation_type);
+        return block_validation_type;
+    }
+
+    save_chain_update(db, &chain_update).await;
+
+    let mined_block_message = Message::BlockMined {
+        from: node.lock().await.address.clone(),
+        block
+    };
+    broadcast_message(node.clone(), &mined_block_message).await;
+
+    block_validation_type
+}
+
+async fn spawn_mining(node: Arc<Mutex<Node>>, mining_flag: Arc<AtomicBool>, mining_threads: usize) -> Option<Block> {
+    let node_address = node.lock().await.wallet.address.clone();
+    let block = create_block_template(node, node_address).await;
+
+    tokio::task::spawn_blocking(move || mine_block(block, mining_flag, mining_threads)).await.unwrap()
+}
+
+fn mine_block(block: Block, cancel_flag: Arc<AtomicBool>, mining_threads: usize) -> Option<Block> {
+    let mined_block = mine_parallel(block, mining_threads, cancel_flag);
+    if let Some(block) = &mined_block {
+        println!("Mined block {}", block.index);
+    }
+
+    mined_block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MINING_REWARD_AMOUNT;
+    use crate::database::operations::MockDatabaseOperations;
+
+    async fn create_node() -> Arc<Mutex<Node>> {
+        let node = Arc::new(Mutex::new(Node::new("test_address".to_string())));
+        node.lock().await.blockchain.create_genesis_block();
+
+        node
+    }
+
+    #[tokio::test]
+    async fn test_selected_template_extends_tip() {
+        let node = create_node().await;
+
+        let mut block = create_block_template_from(node.clone(), "miner_address".to_string(), &[]).await.unwrap();
+        while !block.mine() {}
+
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(node.lock().await.blockchain.is_valid_new_block(&block), BlockValidationType::Valid);
+    }
+
+    #[tokio::test]
+    async fn test_mined_block_completing_heavier_branch_is_saved() {
+        let node = create_node().await;
+        let genesis = node.lock().await.blockchain.get_latest_block().clone();
+        let mine_child = |parent: &Block, miner_address: &str| {
+            let mut block = Block::new(parent.index + 1, parent.hash.clone(), vec![Transaction::new_coinbase(miner_address.to_string(), MINING_REWARD_AMOUNT, parent.index + 1)], miner_address.to_string());
+            while !block.mine() {}
+            block
+        };
+
+        let main_block = mine_child(&genesis, "miner_1");
+        let side_block = mine_child(&genesis, "miner_2");
+        let side_child = mine_child(&side_block, "miner_2");
+        node.lock().await.blockchain.add_block_to_chain(&main_block);
+        node.lock().await.blockchain.add_block_to_chain(&side_child);
+
+        let mut db = MockDatabaseOperations::new();
+        db.expect_delete_blocks_from_index().times(1).returning(|_| true);
+        db.expect_save_block().times(2).returning(|_| true);
+        db.expect_set_user_balance().returning(|_, _| true);
+
+        let block_validation_type = submit_mined_block(node.clone(), Arc::new(db), side_block).await;
+
+        assert_eq!(block_validation_type, BlockValidationType::Reorg);
+        assert_eq!(node.lock().await.blockchain.get_latest_block().hash, side_child.hash);
+    }
+
+    #[tokio::test]
+    async fn test_selected_template_rejects_unknown_transaction() {
+        let node = create_node().await;
+
+        let result = create_block_template_from(node, "miner_address".to_string(), &["missing".to_string()]).await;
+
+        assert_eq!(result.unwrap_err().to_string(), "Transaction missing is not in the mempool");
+    }
+}
@@ -1,2 +1,3 @@
-pub mod mining_reward;
+pub mod mining_engine;
+pub mod mining_jobs;
 pub mod mining_tasks;
\ No newline at end of file
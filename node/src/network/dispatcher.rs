@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use tokio::sync::oneshot;
+use crate::network::message::Message;
+
+/*
+    Routes responses from a peer to the requests waiting for them.
+    Every request carries an id that the peer copies into its response,
+    so responses can arrive in any order and between other messages.
+    Messages no request is waiting for are handed back to be handled normally.
+    When the connection closes every waiting request fails straight away,
+    and requests registered afterwards fail without being sent a response.
+*/
+
+#[derive(Clone, Default)]
+pub struct PendingRequests {
+    next_request_id: Arc<AtomicU64>,
+    waiting: Arc<Mutex<HashMap<u64, oneshot::Sender<Message>>>>,
+    closed: Arc<AtomicBool>
+}
+
+impl PendingRequests {
+    pub fn register(&self) -> (u64, oneshot::Receiver<Message>) {
+        let request_id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        let mut waiting = self.waiting.lock().unwrap();
+        // Dropping the sender makes the receiver fail at once
+        if !self.closed.load(Ordering::Relaxed) {
+            waiting.insert(request_id, sender);
+        }
+
+        (request_id, receiver)
+    }
+
+    pub fn cancel(&self, request_id: u64) {
+        self.waiting.lock().unwrap().remove(&request_id);
+    }
+
+    // Called when the connection closes, dropping the senders fails every waiting request
+    pub fn fail_all(&self) {
+        let mut waiting = self.waiting.lock().unwrap();
+        self.closed.store(true, Ordering::Relaxed);
+        waiting.clear();
+    }
+
+    // Returns the message when it is not a response to a waiting request
+    pub fn resolve(&self, message: Message) -> Option<Message> {
+        let Some(request_id) = message.get_response_id() else {
+            return Some(message);
+        };
+
+        match self.waiting.lock().unwrap().remove(&request_id) {
+            Some(sender) => {
+                // The requester may have stopped waiting, the response is dropped then
+                let _ = sender.send(message);
+                None
+            }
+            None => Some(message)
+        }
+    }
+
+    pub fn get_waiting_count(&self) -> usize {
+        self.waiting.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_response(request_id: u64) -> Message {
+        Message::BlockList { from: "peer".to_string(), request_id, blocks: vec![] }
+    }
+
+    #[tokio::test]
+    async fn test_responses_reach_their_request() {
+        let pending_requests = PendingRequests::default();
+        let (first_id, first_receiver) = pending_requests.register();
+        let (second_id, second_receiver) = pending_requests.register();
+
+        assert!(pending_requests.resolve(create_response(second_id)).is_none());
+        assert!(pending_requests.resolve(create_response(first_id)).is_none());
+
+        assert_eq!(first_receiver.await.unwrap().get_response_id(), Some(first_id));
+        assert_eq!(second_receiver.await.unwrap().get_response_id(), Some(second_id));
+        assert_eq!(pending_requests.get_waiting_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fail_all_fails_waiting_requests() {
+        let pending_requests = PendingRequests::default();
+        let (_, receiver) = pending_requests.register();
+
+        pending_requests.fail_all();
+        let (_, late_receiver) = pending_requests.register();
+
+        assert!(receiver.await.is_err());
+        assert!(late_receiver.await.is_err());
+        assert_eq!(pending_requests.get_waiting_count(), 0);
+    }
+
+    #[test]
+    fn test_unsolicited_messages_are_returned() {
+        let pending_requests = PendingRequests::default();
+        let (request_id, _receiver) = pending_requests.register();
+
+        let gossip = Message::PeerConnectionRequest { from: "peer".to_string() };
+        assert!(pending_requests.resolve(gossip).is_some());
+        assert!(pending_requests.resolve(create_response(request_id + 1)).is_some());
+
+        pending_requests.cancel(request_id);
+        assert!(pending_requests.resolve(create_response(request_id)).is_some());
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use crate::constants::{MAX_MESSAGE_SIZE, NETWORK_MAGIC};
+use crate::network::message::Message;
+
+/*
+    Messages travel between peers as length-prefixed frames:
+    4 magic bytes, the payload length as a big-endian u32,
+    the first 4 bytes of the payload's SHA-256 and then the payload.
+    The length is checked before the payload is read,
+    so a peer cannot make the node buffer more than MAX_MESSAGE_SIZE bytes.
+*/
+
+pub const FRAME_HEADER_SIZE: usize = 12;
+
+pub fn encode_frame(message: &Message) -> Result<Vec<u8>> {
+    let payload = message.to_vec();
+    if payload.len() > MAX_MESSAGE_SIZE {
+        return Err(anyhow!("Message of {} bytes exceeds the maximum of {}", payload.len(), MAX_MESSAGE_SIZE));
+    }
+
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len());
+    frame.extend_from_slice(&NETWORK_MAGIC);
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&checksum(&payload));
+    frame.extend_from_slice(&payload);
+
+    Ok(frame)
+}
+
+// A response too large to encode is replaced by the same response without content,
+// so the requester gets an answer instead of waiting for its timeout
+pub fn encode_response(response: &Message) -> Result<Vec<u8>> {
+    encode_frame(response).or_else(|e| {
+        println!("Sending {} without content: {}", response.get_type(), e);
+        let empty_response = response.without_content().ok_or(e)?;
+        encode_frame(&empty_response)
+    })
+}
+
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, message: &Message) -> Result<()> {
+    write_encoded_frame(writer, &encode_frame(message)?).await
+}
+
+pub async fn write_encoded_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: &[u8]) -> Result<()> {
+    writer.write_all(frame).await?;
+    writer.flush().await?;
+
+    Ok(())
+}
+
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Message> {
+    let mut header = [0u8; FRAME_HEADER_SIZE];
+    reader.read_exact(&mut header).await?;
+
+    if header[0..4] != NETWORK_MAGIC {
+        return Err(anyhow!("Frame does not start with the network magic bytes"));
+    }
+
+    let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if length > MAX_MESSAGE_SIZE {
+        return Err(anyhow!("Frame of {} bytes exceeds the maximum of {}", length, MAX_MESSAGE_SIZE));
+    }
+
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload).await?;
+
+    if header[8..12] != checksum(&payload) {
+        return Err(anyhow!("Frame checksum does not match its payload"));
+    }
+
+    Message::from_bytes(&payload)
+}
+
+// True when the error only means the peer closed the connection between frames
+pub fn is_connection_closed(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(payload);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chain::block::Block;
+    use super::*;
+
+    fn create_message() -> Message {
+        Message::PeerConnectionRequest { from: "node1".to_string() }
+    }
+
+    #[tokio::test]
+    async fn test_frame_round_trip() {
+        let mut buffer = vec![];
+        write_frame(&mut buffer, &create_message()).await.unwrap();
+
+        assert_eq!(&buffer[0..4], &NETWORK_MAGIC);
+        match read_frame(&mut buffer.as_slice()).await.unwrap() {
+            Message::PeerConnectionRequest { from } => assert_eq!(from, "node1"),
+            _ => panic!("Decoded to wrong variant")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_frames_are_rejected() {
+        let frame = encode_frame(&create_message()).unwrap();
+
+        let mut wrong_magic = frame.clone();
+        wrong_magic[0] = b'X';
+        assert!(read_frame(&mut wrong_magic.as_slice()).await.is_err());
+
+        let mut corrupted_payload = frame.clone();
+        *corrupted_payload.last_mut().unwrap() ^= 1;
+        assert!(read_frame(&mut corrupted_payload.as_slice()).await.is_err());
+
+        let mut oversized = frame.clone();
+        oversized[4..8].copy_from_slice(&(MAX_MESSAGE_SIZE as u32 + 1).to_be_bytes());
+        assert!(read_frame(&mut oversized.as_slice()).await.is_err());
+
+        let truncated = &frame[..frame.len() - 1];
+        assert!(read_frame(&mut &truncated[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_response_is_sent_without_content() {
+        let block = Block::new(1, "previous_hash".to_string(), vec![], "a".repeat(MAX_MESSAGE_SIZE));
+        let response = Message::BlockList { from: "node1".to_string(), request_id: 7, blocks: vec![block] };
+        assert!(encode_frame(&response).is_err());
+
+        let frame = encode_response(&response).unwrap();
+        match read_frame(&mut frame.as_slice()).await.unwrap() {
+            Message::BlockList { request_id, blocks, .. } => {
+                assert_eq!(request_id, 7);
+                assert!(blocks.is_empty());
+            }
+            _ => panic!("Decoded to wrong variant")
+        }
+
+        let oversized_request = Message::PeerConnectionRequest { from: "a".repeat(MAX_MESSAGE_SIZE) };
+        assert!(encode_response(&oversized_request).is_err());
+    }
+}
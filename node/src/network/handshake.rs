@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+use crate::constants::{HANDSHAKE_TIMEOUT, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use crate::network::frame::{read_frame, write_frame};
+use crate::network::message::{Message, Version};
+use crate::node::Node;
+
+/*
+    Version handshake run before any other message on a connection.
+    The connecting side sends its version first and the accepting side replies with its own.
+    Either side closes the connection when the peer is on another network
+    or speaks a protocol older than MIN_PROTOCOL_VERSION.
+    Both then use the lower of the two protocol versions.
+*/
+
+pub fn create_version(node: &Node) -> Version {
+    Version {
+        from: node.address.clone(),
+        protocol_version: PROTOCOL_VERSION,
+        network_id: node.blockchain.spec.network_id.clone(),
+        best_height: node.blockchain.get_length().saturating_sub(1) as u64,
+        services: node.services
+    }
+}
+
+pub async fn send_handshake<W, R>(local_version: &Version, writer: &mut W, reader: &mut R) -> Result<Version>
+where
+    W: AsyncWrite + Unpin,
+    R: AsyncRead + Unpin
+{
+    write_frame(writer, &Message::Version(local_version.clone())).await?;
+    let remote_version = read_version(reader).await?;
+    check_version(local_version, &remote_version)?;
+
+    Ok(remote_version)
+}
+
+pub async fn accept_handshake<W, R>(node: Arc<Mutex<Node>>, writer: &mut W, reader: &mut R) -> Result<Version>
+where
+    W: AsyncWrite + Unpin,
+    R: AsyncRead + Unpin
+{
+    let remote_version = read_version(reader).await?;
+    let local_version = create_version(&*node.lock().await);
+    // Replying before checking lets a rejected peer see why
+    write_frame(writer, &Message::Version(local_version.clone())).await?;
+    check_version(&local_version, &remote_version)?;
+
+    Ok(remote_version)
+}
+
+pub fn negotiate_protocol_version(remote_version: &Version) -> u32 {
+    remote_version.protocol_version.min(PROTOCOL_VERSION)
+}
+
+fn check_version(local_version: &Version, remote_version: &Version) -> Result<()> {
+    if remote_version.network_id != local_version.network_id {
+        return Err(anyhow!("Peer {} is on network {}, expected {}", remote_version.from, remote_version.network_id, local_version.network_id));
+    }
+
+    if remote_version.protocol_version < MIN_PROTOCOL_VERSION {
+        return Err(anyhow!("Peer {} speaks protocol {}, the minimum is {}", remote_version.from, remote_version.protocol_version, MIN_PROTOCOL_VERSION));
+    }
+
+    Ok(())
+}
+
+async fn read_version<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Version> {
+    match timeout(Duration::from_secs(HANDSHAKE_TIMEOUT), read_frame(reader)).await?? {
+        Message::Version(version) => Ok(version),
+        message => Err(anyhow!("Expected a version message but received {}", message.get_type()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, split};
+
+    fn create_version_for(network_id: &str) -> Version {
+        Version {
+            from: "connecting_node".to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            network_id: network_id.to_string(),
+            best_height: 0,
+            services: 0
+        }
+    }
+
+    async fn run_handshake(local_version: Version) -> (Result<Version>, Result<Version>) {
+        let node = Arc::new(Mutex::new(Node::new("accepting_node".to_string())));
+        let (connecting_stream, accepting_stream) = duplex(1024);
+
+        let accepting = tokio::spawn(async move {
+            let (mut reader, mut writer) = split(accepting_stream);
+            accept_handshake(node, &mut writer, &mut reader).await
+        });
+        let (mut reader, mut writer) = split(connecting_stream);
+        let connecting = send_handshake(&local_version, &mut writer, &mut reader).await;
+        drop((reader, writer));
+
+        (connecting, accepting.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_handshake_exchanges_versions() {
+        let network_id = Node::new("accepting_node".to_string()).blockchain.spec.network_id.clone();
+
+        let (connecting, accepting) = run_handshake(create_version_for(&network_id)).await;
+
+        assert_eq!(connecting.unwrap().from, "accepting_node");
+        assert_eq!(accepting.unwrap().from, "connecting_node");
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_other_network() {
+        let (connecting, accepting) = run_handshake(create_version_for("other_network")).await;
+
+        assert!(connecting.unwrap_err().to_string().contains("other_network"));
+        assert!(accepting.unwrap_err().to_string().contains("other_network"));
+    }
+}
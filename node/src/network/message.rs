@@ -1,31 +1,86 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use crate::chain::block::Block;
+use crate::chain::block::{Block, BlockHeader};
+use crate::chain::transaction::Transaction;
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Message {
+    Version(Version),
     PeerConnectionRequest { from: String },
     PeerConnectionResponse { from: String, known_addresses: Vec<String> },
     BlockMined { from: String, block: Block },
     GenesisBlock { from: String, genesis_block: Block },
-    FullChainRequest { from: String },
-    FullChainResponse { from: String, blocks: Vec<Block> },
-    ChainLengthRequest { from: String },
-    ChainLengthResponse { from: String, length: usize },
-    BlockHashesRequest { from: String, hashes: Vec<String> },
-    BlockHashesResponse { from: String, hashes: Vec<String>, common_index: usize },
-    GetBlocks { from: String, hashes: Vec<String> },
-    BlockList { from: String, blocks: Vec<Block> }
+    GetHeaders { from: String, request_id: u64, locator: Vec<String> },
+    Headers { from: String, request_id: u64, headers: Vec<BlockHeader> },
+    BlockHashesRequest { from: String, request_id: u64, hashes: Vec<String> },
+    BlockHashesResponse { from: String, request_id: u64, hashes: Vec<String>, common_index: usize },
+    GetBlocks { from: String, request_id: u64, hashes: Vec<String> },
+    BlockList { from: String, request_id: u64, blocks: Vec<Block> },
+    NewTransaction { from: String, transaction: Transaction }
 }
 
-#[derive(Clone)]
-pub struct ChainLength {
+// First message on every connection, services is a bit set of the SERVICE_* constants
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Version {
     pub from: String,
-    pub length: usize
+    pub protocol_version: u32,
+    pub network_id: String,
+    pub best_height: u64,
+    pub services: u64
 }
 
 impl Message {
+    pub fn get_type(&self) -> &'static str {
+        match self {
+            Message::Version(_) => "Version",
+            Message::PeerConnectionRequest { .. } => "PeerConnectionRequest",
+            Message::PeerConnectionResponse { .. } => "PeerConnectionResponse",
+            Message::BlockMined { .. } => "BlockMined",
+            Message::GenesisBlock { .. } => "GenesisBlock",
+            Message::GetHeaders { .. } => "GetHeaders",
+            Message::Headers { .. } => "Headers",
+            Message::BlockHashesRequest { .. } => "BlockHashesRequest",
+            Message::BlockHashesResponse { .. } => "BlockHashesResponse",
+            Message::GetBlocks { .. } => "GetBlocks",
+            Message::BlockList { .. } => "BlockList",
+            Message::NewTransaction { .. } => "NewTransaction"
+        }
+    }
+
+    // Id of the request this message answers, responses copy it from their request
+    pub fn get_response_id(&self) -> Option<u64> {
+        match self {
+            Message::Headers { request_id, .. }
+            | Message::BlockHashesResponse { request_id, .. }
+            | Message::BlockList { request_id, .. } => Some(*request_id),
+            _ => None
+        }
+    }
+
+    // The response with its content removed, None for messages that are not responses
+    pub fn without_content(&self) -> Option<Message> {
+        match self {
+            Message::Headers { from, request_id, .. } => Some(Message::Headers {
+                from: from.clone(),
+                request_id: *request_id,
+                headers: vec![]
+            }),
+            Message::BlockHashesResponse { from, request_id, common_index, .. } => Some(Message::BlockHashesResponse {
+                from: from.clone(),
+                request_id: *request_id,
+                hashes: vec![],
+                common_index: *common_index
+            }),
+            Message::BlockList { from, request_id, .. } => Some(Message::BlockList {
+                from: from.clone(),
+                request_id: *request_id,
+                blocks: vec![]
+            }),
+            _ => None
+        }
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
         serde_json::from_slice(bytes).map_err(Into::into)
     }
@@ -47,6 +102,7 @@ mod tests {
             hash: "hash".to_string(),
             timestamp: 123456789,
             transactions: vec![],
+            merkle_root: "merkle_root".to_string(),
             miner_address: "miner1".to_string(),
             nonce: 42,
             difficulty: 0,
@@ -65,14 +121,31 @@ mod tests {
     }
 
     #[test]
-    fn test_chain_length_response_serialization() {
-        let msg = Message::ChainLengthResponse { from: "node2".to_string(), length: 10 };
+    fn test_block_hashes_response_serialization() {
+        let msg = Message::BlockHashesResponse { from: "node2".to_string(), request_id: 7, hashes: vec!["hash".to_string()], common_index: 10 };
         let serialized = serde_json::to_vec(&msg).unwrap();
         let deserialized: Message = Message::from_bytes(&serialized).unwrap();
         match deserialized {
-            Message::ChainLengthResponse { from, length } => {
+            Message::BlockHashesResponse { from, request_id, hashes, common_index } => {
                 assert_eq!(from, "node2");
-                assert_eq!(length, 10);
+                assert_eq!(request_id, 7);
+                assert_eq!(hashes, vec!["hash".to_string()]);
+                assert_eq!(common_index, 10);
+            },
+            _ => panic!("Deserialized to wrong variant"),
+        }
+    }
+
+    #[test]
+    fn test_new_transaction_serialization() {
+        let transaction = Transaction::new_coinbase("recipient".to_string(), 10, 1);
+        let msg = Message::NewTransaction { from: "node3".to_string(), transaction: transaction.clone() };
+        let serialized = serde_json::to_vec(&msg).unwrap();
+        let deserialized: Message = Message::from_bytes(&serialized).unwrap();
+        match deserialized {
+            Message::NewTransaction { from, transaction: t } => {
+                assert_eq!(from, "node3");
+                assert_eq!(t, transaction);
             },
             _ => panic!("Deserialized to wrong variant"),
         }
@@ -1,146 +1,123 @@
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
 use tokio::sync::Mutex;
 use crate::chain::block::Block;
 use crate::chain::block_validation_type::BlockValidationType;
-use crate::constants::{MINING_REWARD_AMOUNT, MINING_REWARD_DELAY};
 use crate::database::operations::DbOperations;
 use crate::database::validator::Validator;
-use crate::mining::mining_reward::MiningReward;
-use crate::mining::mining_tasks::spawn_update_balances;
-use crate::network::message::{ChainLength, Message};
-use crate::network::message_sender::{broadcast_message, send_message};
 use crate::node::Node;
-use crate::tasks::fork_handling::wait_and_send_block_hashes;
+use crate::tasks::fork_handling::send_block_hashes_request;
+use crate::tasks::persistence_tasks::save_chain_update;
 
-pub async fn on_genesis_received(node: Arc<Mutex<Node>>, from: String, genesis_block: Block) {
-    tokio::time::sleep(Duration::from_millis(1000)).await;
-    node.lock().await.blockchain.load_starting_block(genesis_block);
+// The chain is checked and loaded under one lock so concurrent genesis messages cannot both be applied
+pub async fn on_genesis_received(node: Arc<Mutex<Node>>, db: DbOperations, from: String, genesis_block: Block) {
+    {
+        let mut locked_node = node.lock().await;
+        if locked_node.blockchain.get_length() > 0 {
+            println!("Ignoring starting block from {}, chain already loaded", from);
+            return;
+        }
+
+        if !locked_node.blockchain.is_expected_genesis(&genesis_block) {
+            println!("Rejecting genesis block {} from {}, it does not match the chain spec", genesis_block.hash, from);
+            return;
+        }
+
+        if !locked_node.blockchain.load_starting_block(genesis_block.clone()) {
+            return;
+        }
+    }
+
+    db.save_block(&genesis_block).await;
     println!("Starting block received from {}", from);
     println!("Starting mining...");
 }
 
-pub async fn on_block_received(node: Arc<Mutex<Node>>, mining_flag: Arc<AtomicBool>, validator: Arc<Validator>, from: String, block: Block) {
+pub async fn on_block_received(node: Arc<Mutex<Node>>, mining_flag: Arc<AtomicBool>, validator: Arc<Validator>, from: String, block: Block) -> BlockValidationType {
     if node.lock().await.blockchain_locked {
         // Save block & exit if blockchain is not ready
-        node.lock().await.blockchain.add_pending_block(block.clone());
-        return;
+        node.lock().await.blockchain.add_orphan_block(block.clone());
+        return BlockValidationType::Fork;
     }
 
-    for transaction in &block.transactions {
-        if !validator.validate_transaction(transaction).await {
-            println!("Invalid transaction received... Continuing to mine");
-            return;
-        }
-    }
+    let (block_validation_type, chain_update) = {
+        let mut locked_node = node.lock().await;
+        let result = locked_node.receive_block(&block);
+        locked_node.reconcile_mempool(&result.1).await;
+        result
+    };
+    save_chain_update(validator.db.clone(), &chain_update).await;
 
-    let block_validation_type = node.lock().await.receive_block(&block);
     if block_validation_type == BlockValidationType::Valid {
         mining_flag.store(false, Ordering::Relaxed);
         println!("Valid block received from {}... Stopping mining", from);
-
-        let mining_reward = MiningReward::new(MINING_REWARD_AMOUNT, block.miner_address, block.index + MINING_REWARD_DELAY);
-        validator.db.save_mining_reward(mining_reward).await;
-        spawn_update_balances(validator.db.clone(), block.transactions);
-        apply_mining_reward(validator.db.clone(), block.index);
+    } else if block_validation_type == BlockValidationType::Reorg {
+        mining_flag.store(false, Ordering::Relaxed);
+        println!("Block from {} completed a heavier chain... Switched to new chain tip", from);
+    } else if block_validation_type == BlockValidationType::SideChain {
+        println!("Block from {} stored on a side chain... Continuing to mine", from);
     } else if block_validation_type == BlockValidationType::Fork {
-        println!("Fork detected...");
-
-        let (address, blockchain, max_peer_chain_length) = {
-            let locked_node = node.lock().await;
-            (locked_node.address.clone(), locked_node.blockchain.clone(), locked_node.max_peer_chain_length.clone())
-        };
-        if blockchain.invalid_blocks.len() >= 5 && max_peer_chain_length.is_none() {
-            println!("5+ forked blocks detected... Resolving fork.");
-            mining_flag.store(false, Ordering::Relaxed);
-
-            let message = Message::ChainLengthRequest { from: address.clone() };
-            broadcast_message(node.clone(), &message).await;
-
-            tokio::spawn(wait_and_send_block_hashes(node.clone()));
-        } else {
-            println!("Continuing to mine...");
-        }
-    } else {
-        println!("Invalid block received from {}... Continuing to mine", from);
-    }
-}
-
-pub async fn on_chain_length_request(node: Arc<Mutex<Node>>, from: String) {
-    tokio::spawn(async move {
-        let mut locked_node = node.lock().await;
-        let chain_length = locked_node.blockchain.get_length();
-        let message = Message::ChainLengthResponse { from: locked_node.address.clone(), length: chain_length };
-        let recipient_node = locked_node.get_peer(&from);
-
-        if let Some(peer) = recipient_node {
-            send_message(&message, &mut peer.writer).await;
-        } else {
-            println!("No peer found with address: {}", from);
-        }
-    });
-}
-
-pub async fn on_chain_length_response(node: Arc<Mutex<Node>>, message: ChainLength) {
-    let current_max_length = node.lock().await.max_peer_chain_length.clone();
-
-    if current_max_length.is_none() || message.length > current_max_length.unwrap().length {
-        node.lock().await.max_peer_chain_length = Some(ChainLength {
-            from: message.from.clone(),
-            length: message.length
-        });
-        println!("Updated max_peer_chain_length to {} from peer {}", message.length, message.from);
-        return;
+        println!("Fork detected... Requesting missing blocks from {}", from);
+        // Resolved in the background so this connection keeps processing messages
+        tokio::spawn(send_block_hashes_request(node.clone(), validator.db.clone(), mining_flag.clone(), from.clone()));
+    } else if let BlockValidationType::Invalid(reason) = &block_validation_type {
+        println!("Invalid block received from {} ({})... Continuing to mine", from, reason);
     }
-}
-
-fn apply_mining_reward(db: DbOperations, block_index: u64) {
-    tokio::spawn(async move {
-        println!("Applying mining reward for inbound block...");
-        let db_response = db.get_mining_reward_at_block_index(block_index).await;
-        if db_response.is_err() {
-            return;
-        }
 
-        let recipient_address = db_response.unwrap().recipient_address;
-        db.create_user_and_update_balance(recipient_address, MINING_REWARD_AMOUNT as i64).await;
-    });
+    block_validation_type
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chain::block_validation_type::InvalidBlockReason;
     use crate::database::operations::MockDatabaseOperations;
 
     #[tokio::test]
     async fn test_on_genesis_received() {
         let node = Arc::new(Mutex::new(Node::new("test_address".to_string())));
-        let genesis_block = Block::new(0, "0".to_string(), vec![], "miner_address".to_string());
+        let genesis_block = node.lock().await.blockchain.get_genesis_block().clone();
+        let mut db = MockDatabaseOperations::new();
+        db.expect_save_block().returning(|_| true);
+        db.expect_set_user_balance().returning(|_, _| true);
 
-        on_genesis_received(node.clone(), "test_peer".to_string(), genesis_block.clone()).await;
+        on_genesis_received(node.clone(), Arc::new(db), "test_peer".to_string(), genesis_block.clone()).await;
 
         let locked_node = node.lock().await;
         assert_eq!(locked_node.blockchain.get_length(), 1);
         assert_eq!(locked_node.blockchain.chain[0], genesis_block);
     }
 
+    #[tokio::test]
+    async fn test_on_unexpected_genesis_received() {
+        let node = Arc::new(Mutex::new(Node::new("test_address".to_string())));
+        let mut genesis_block = Block::new(0, "0".to_string(), vec![], "miner_address".to_string());
+        while !genesis_block.mine() {}
+        let db = MockDatabaseOperations::new();
+
+        on_genesis_received(node.clone(), Arc::new(db), "test_peer".to_string(), genesis_block).await;
+
+        assert_eq!(node.lock().await.blockchain.get_length(), 0);
+    }
+
     #[tokio::test]
     async fn test_on_block_received_blockchain_locked() {
         let node = Arc::new(Mutex::new(Node::new("test_address".to_string())));
         let mining_flag = Arc::new(AtomicBool::new(true));
         let mut db = MockDatabaseOperations::new();
-        db.expect_save_mining_reward().returning(|_| true);
+        db.expect_save_block().returning(|_| true);
+        db.expect_set_user_balance().returning(|_, _| true);
 
         let validator = Arc::new(Validator::new(Arc::new(db)));
-        let block = Block::new(1, "0".to_string(), vec![], "miner_address".to_string());
+        let mut block = Block::new(1, "0".to_string(), vec![], "miner_address".to_string());
+        while !block.mine() {}
 
         on_block_received(node.clone(), mining_flag.clone(), validator.clone(), "test_peer".to_string(), block.clone()).await;
 
         let locked_node = node.lock().await;
         assert_eq!(locked_node.blockchain.get_length(), 0);
-        assert_eq!(locked_node.blockchain.pending_blocks.len(), 1);
-        assert_eq!(locked_node.blockchain.pending_blocks[0], block);
+        assert_eq!(locked_node.blockchain.block_tree.get_orphan_count(), 1);
+        assert_eq!(locked_node.blockchain.block_tree.orphans["0"][0], block);
     }
 
     #[tokio::test]
@@ -148,17 +125,24 @@ mod tests {
         let node = Arc::new(Mutex::new(Node::new("test_address".to_string())));
         let mining_flag = Arc::new(AtomicBool::new(true));
         let mut db = MockDatabaseOperations::new();
-        db.expect_save_mining_reward().returning(|_| true);
+        db.expect_save_block().returning(|_| true);
+        db.expect_set_user_balance().returning(|_, _| true);
 
         let validator = Arc::new(Validator::new(Arc::new(db)));
-        let block = Block::new(0, "0".to_string(), vec![], "miner_address".to_string());
+        let mut other_genesis = Block::new(0, "0".to_string(), vec![], "miner_address".to_string());
+        while !other_genesis.mine() {}
+        let genesis = node.lock().await.blockchain.get_genesis_block().clone();
         node.lock().await.blockchain_locked = false;
 
-        on_block_received(node.clone(), mining_flag.clone(), validator.clone(), "test_peer".to_string(), block.clone()).await;
+        let block_validation_type = on_block_received(node.clone(), mining_flag.clone(), validator.clone(), "test_peer".to_string(), other_genesis).await;
+        assert_eq!(block_validation_type, BlockValidationType::Invalid(InvalidBlockReason::UnexpectedGenesis));
+        assert_eq!(node.lock().await.blockchain.get_length(), 0);
+
+        on_block_received(node.clone(), mining_flag.clone(), validator.clone(), "test_peer".to_string(), genesis.clone()).await;
 
         let locked_node = node.lock().await;
         assert_eq!(locked_node.blockchain.get_length(), 1);
-        assert_eq!(locked_node.blockchain.chain[0], block);
+        assert_eq!(locked_node.blockchain.chain[0], genesis);
     }
 
     #[tokio::test]
@@ -166,7 +150,8 @@ mod tests {
         let node = Arc::new(Mutex::new(Node::new("test_address".to_string())));
         let mining_flag = Arc::new(AtomicBool::new(true));
         let mut db = MockDatabaseOperations::new();
-        db.expect_save_mining_reward().returning(|_| true);
+        db.expect_save_block().returning(|_| true);
+        db.expect_set_user_balance().returning(|_, _| true);
 
         let validator = Arc::new(Validator::new(Arc::new(db)));
         let genesis = Block::new(0, "0".to_string(), vec![], "miner_address".to_string());
@@ -186,19 +171,21 @@ mod tests {
         let node = Arc::new(Mutex::new(Node::new("test_address".to_string())));
         let mining_flag = Arc::new(AtomicBool::new(true));
         let mut db = MockDatabaseOperations::new();
-        db.expect_save_mining_reward().returning(|_| true);
+        db.expect_save_block().returning(|_| true);
+        db.expect_set_user_balance().returning(|_, _| true);
 
         let validator = Arc::new(Validator::new(Arc::new(db)));
         let genesis = Block::new(0, "0".to_string(), vec![], "miner_address".to_string());
         node.lock().await.blockchain_locked = false;
         node.lock().await.blockchain.load_starting_block(genesis.clone());
-        let block = Block::new(1, "invalid_hash".to_string(), vec![], "miner_address".to_string());
+        let mut block = Block::new(1, "invalid_hash".to_string(), vec![], "miner_address".to_string());
+        while !block.mine() {}
 
         on_block_received(node.clone(), mining_flag.clone(), validator.clone(), "test_peer".to_string(), block.clone()).await;
 
         let locked_node = node.lock().await;
-        assert_eq!(locked_node.blockchain.invalid_blocks.len(), 1);
-        assert_eq!(locked_node.blockchain.invalid_blocks[0], block);
+        assert_eq!(locked_node.blockchain.block_tree.get_orphan_count(), 1);
+        assert_eq!(locked_node.blockchain.block_tree.orphans["invalid_hash"][0], block);
         assert_eq!(locked_node.blockchain.chain.len(), 1);
         assert_eq!(locked_node.blockchain.chain[0], genesis);
     }
@@ -1,49 +1,59 @@
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use tokio::net::tcp::OwnedWriteHalf;
 use tokio::sync::Mutex;
+use tokio::time::timeout;
+use crate::constants::REQUEST_TIMEOUT;
+use crate::network::frame::write_frame;
 use crate::network::message::Message;
+use crate::network::peer::PeerWriter;
 use crate::node::Node;
 
 pub async fn broadcast_message(node: Arc<Mutex<Node>>, message: &Message) {
-    let mut node = node.lock().await;
+    broadcast_message_except(node, message, None).await;
+}
+
+// Writers are collected first so the node is not locked while writing to slow peers
+pub async fn broadcast_message_except(node: Arc<Mutex<Node>>, message: &Message, excluded_address: Option<&str>) {
+    let writers: Vec<PeerWriter> = node.lock().await.peers
+        .iter()
+        .filter(|(address, _)| Some(address.as_str()) != excluded_address)
+        .map(|(_, peer)| peer.writer.clone())
+        .collect();
 
-    for (_, peer) in &mut node.peers {
-        send_message(message, &mut peer.writer).await;
+    for writer in writers {
+        send_message(message, &mut *writer.lock().await).await;
     }
 }
 
 pub async fn send_message(message: &Message, writer: &mut OwnedWriteHalf) {
-    let mut msg_bytes = message.to_vec();
-    msg_bytes.push(b'\n');
-    if let Err(e) = writer.write_all(&msg_bytes).await {
+    if let Err(e) = write_frame(writer, message).await {
         println!("Failed to write to peer: {:?}", e);
-        return;
     }
 }
 
-pub async fn send_message_expect_response(message: &Message, writer: &mut OwnedWriteHalf, reader: &mut OwnedReadHalf) -> Option<Message> {
-    let mut msg_bytes = message.to_vec();
-    msg_bytes.push(b'\n');
-    if let Err(e) = writer.write_all(&msg_bytes).await {
-        println!("Failed to write to peer: {:?}", e);
-        return None;
-    } else if writer.flush().await.is_err() {
-        return None;
+// create_request builds the request from the id its response will carry.
+// The node is only locked while looking up the peer, not while writing or waiting for the response.
+pub async fn send_request(node: Arc<Mutex<Node>>, peer_address: &str, create_request: impl FnOnce(u64) -> Message) -> Result<Message> {
+    let (writer, pending_requests) = {
+        let mut locked_node = node.lock().await;
+        let peer = locked_node.get_peer(peer_address).ok_or(anyhow!("Not connected to peer {}", peer_address))?;
+        (peer.writer.clone(), peer.pending_requests.clone())
+    };
+
+    let (request_id, receiver) = pending_requests.register();
+    if let Err(e) = write_frame(&mut *writer.lock().await, &create_request(request_id)).await {
+        pending_requests.cancel(request_id);
+        return Err(e);
     }
 
-    let mut buf_reader = BufReader::new(reader);
-    let mut response = String::new();
-    match buf_reader.read_line(&mut response).await {
-        Ok(0) => None,
-        Ok(_) => {
-            if let Ok(message) = Message::from_bytes(response.as_bytes()) {
-                Some(message)
-            } else {
-                println!("Failed to parse response message");
-                None
-            }
-        },
-        Err(_) => None,
+    match timeout(Duration::from_secs(REQUEST_TIMEOUT), receiver).await {
+        Ok(Ok(response)) => Ok(response),
+        Ok(Err(_)) => Err(anyhow!("Connection to peer {} closed before it responded", peer_address)),
+        Err(_) => {
+            pending_requests.cancel(request_id);
+            Err(anyhow!("Peer {} did not respond within {} seconds", peer_address, REQUEST_TIMEOUT))
+        }
     }
-}
\ No newline at end of file
+}
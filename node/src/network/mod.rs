@@ -1,5 +1,8 @@
 pub mod tcp_connection;
+pub mod dispatcher;
+pub mod frame;
+pub mod handshake;
 pub mod message;
-mod message_receiver;
+pub(crate) mod message_receiver;
 pub mod message_sender;
 pub mod peer;
\ No newline at end of file
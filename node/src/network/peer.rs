@@ -1,17 +1,30 @@
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use std::sync::Arc;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::sync::Mutex;
+use crate::network::dispatcher::PendingRequests;
+use crate::network::handshake::negotiate_protocol_version;
+use crate::network::message::Version;
 
+// Shared so messages can be written after the node lock is released
+pub type PeerWriter = Arc<Mutex<OwnedWriteHalf>>;
+
+// The peer's reader is owned by its reader task, which resolves pending requests
 pub struct Peer {
     pub address: String,
-    pub writer: OwnedWriteHalf,
-    pub reader: OwnedReadHalf
+    pub writer: PeerWriter,
+    pub pending_requests: PendingRequests,
+    pub version: Version,
+    pub protocol_version: u32
 }
 
 impl Peer {
-    pub fn new(address: String, writer: OwnedWriteHalf, reader: OwnedReadHalf) -> Self {
+    pub fn new(address: String, writer: OwnedWriteHalf, pending_requests: PendingRequests, version: Version) -> Self {
         Self {
             address,
-            writer,
-            reader
+            writer: Arc::new(Mutex::new(writer)),
+            pending_requests,
+            protocol_version: negotiate_protocol_version(&version),
+            version
         }
     }
-}
\ No newline at end of file
+}
@@ -1,83 +1,151 @@
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::sync::Mutex;
 use crate::args::args::Args;
 use crate::args::mode::Mode;
+use crate::args::node_type::NodeType;
+use crate::chain::blockchain::Blockchain;
+use crate::constants::{SERVICE_API, SERVICE_BLOCKS, SERVICE_MINING};
 use crate::database::validator::Validator;
-use crate::network::message::{ChainLength, Message};
-use crate::network::message_receiver::{on_block_received, on_chain_length_request, on_chain_length_response, on_genesis_received};
+use crate::network::dispatcher::PendingRequests;
+use crate::network::frame::{encode_response, is_connection_closed, read_frame, write_encoded_frame};
+use crate::network::handshake::{accept_handshake, negotiate_protocol_version};
+use crate::network::message::Message;
+use crate::network::message_receiver::{on_block_received, on_genesis_received};
 use crate::node::Node;
-use crate::tasks::fork_handling::{get_blocks_with_hash, on_block_hashes_request, on_block_hashes_response};
-use crate::tasks::new_node_tasks::create_full_chain_response;
+use crate::mempool::Mempool;
+use crate::tasks::fork_handling::{get_blocks_with_hash, on_block_hashes_request};
+use crate::tasks::block_sync::on_get_headers;
+use crate::tasks::transaction_tasks::on_transaction_received;
 use crate::tasks::peer_connection::{spawn_connect_to_many_peers, spawn_peer_connection_task, spawn_initial_peer_connection};
 
 async fn handle_client(stream: TcpStream, node: Arc<Mutex<Node>>, validator: Arc<Validator>, mining_flag: Arc<AtomicBool>) {
-    let (reader, mut writer) = stream.into_split();
-    let mut lines = BufReader::new(reader).lines();
+    let (mut reader, mut writer) = stream.into_split();
+    let remote_version = match accept_handshake(node.clone(), &mut writer, &mut reader).await {
+        Ok(version) => version,
+        Err(e) => {
+            println!("Rejected connection: {}", e);
+            return;
+        }
+    };
+    println!(
+        "Connection from {} using protocol {} at height {}",
+        remote_version.from, negotiate_protocol_version(&remote_version), remote_version.best_height
+    );
 
     loop {
-        if let Ok(Some(line)) = lines.next_line().await {
-            let node_address = node.lock().await.address.clone();
-            println!("{} received line: {}", node_address, line);
-
-            if let Ok(message) = serde_json::from_str::<Message>(&line) {
-                match message {
-                    Message::PeerConnectionRequest { from } => {
-                        spawn_peer_connection_task(node.clone(), &from);
-                    }
-                    Message::PeerConnectionResponse { from: _, known_addresses } => {
-                        spawn_connect_to_many_peers(node.clone(), known_addresses);
-                    }
-                    Message::FullChainRequest { from: _ } => {
-                        let response = create_full_chain_response(node.clone()).await;
-                        send_response(&mut writer, response).await;
-                    }
-                    Message::GenesisBlock { from, genesis_block } => {
-                        on_genesis_received(node.clone(), from, genesis_block).await;
-                    }
-                    Message::BlockMined { from, block } => {
-                        on_block_received(node.clone(), mining_flag.clone(), validator.clone(), from, block).await;
-                    }
-                    Message::ChainLengthRequest { from } => {
-                        on_chain_length_request(node.clone(), from).await;
-                    }
-                    Message::ChainLengthResponse { from, length } => {
-                        let chain_length_message = ChainLength { from, length };
-                        on_chain_length_response(node.clone(), chain_length_message).await;
-                    }
-                    Message::BlockHashesRequest { from, hashes } => {
-                        on_block_hashes_request(node.clone(), from, hashes).await;
-                    }
-                    Message::BlockHashesResponse { from, hashes, common_index } => {
-                        on_block_hashes_response(node.clone(), mining_flag.clone(), from, hashes, common_index).await;
-                    }
-                    Message::GetBlocks { from: _, hashes } => {
-                        let blocks_to_send = get_blocks_with_hash(node.clone(), hashes).await;
-                        let response = Message::BlockList {
-                            from: node.lock().await.address.clone(),
-                            blocks: blocks_to_send
-                        };
-                        send_response(&mut writer, response).await;
-                    }
-                    _ => {
-                        println!("Received unknown message");
+        let message = match read_frame(&mut reader).await {
+            Ok(message) => message,
+            Err(e) => {
+                if !is_connection_closed(&e) {
+                    println!("Closing connection from {}: {}", remote_version.from, e);
+                }
+                break;
+            }
+        };
+        println!("Received {} from {}", message.get_type(), remote_version.from);
+
+        if let Some(response) = handle_message(node.clone(), validator.clone(), mining_flag.clone(), message).await {
+            send_response(&mut writer, response).await;
+        }
+    }
+}
+
+// Reads a connection this node opened, responses go to the requests waiting for them
+pub fn spawn_peer_reader(
+    node: Arc<Mutex<Node>>,
+    validator: Arc<Validator>,
+    mining_flag: Arc<AtomicBool>,
+    peer_address: String,
+    mut reader: OwnedReadHalf,
+    pending_requests: PendingRequests
+) {
+    tokio::spawn(async move {
+        loop {
+            let message = match read_frame(&mut reader).await {
+                Ok(message) => message,
+                Err(e) => {
+                    if !is_connection_closed(&e) {
+                        println!("Closing connection to {}: {}", peer_address, e);
                     }
+                    break;
                 }
-            } else {
-                println!("Failed to deserialize line.");
+            };
+
+            let Some(message) = pending_requests.resolve(message) else {
+                continue;
+            };
+            println!("Received unsolicited {} from {}", message.get_type(), peer_address);
+
+            let Some(response) = handle_message(node.clone(), validator.clone(), mining_flag.clone(), message).await else {
+                continue;
+            };
+
+            // The node is unlocked before writing so a slow peer cannot stall it
+            let writer = node.lock().await.get_peer(&peer_address).map(|peer| peer.writer.clone());
+            if let Some(writer) = writer {
+                send_response(&mut *writer.lock().await, response).await;
             }
         }
+
+        println!("Disconnected from peer {}", peer_address);
+        node.lock().await.peers.remove(&peer_address);
+        pending_requests.fail_all();
+    });
+}
+
+// Returns the response to send back for request messages
+async fn handle_message(node: Arc<Mutex<Node>>, validator: Arc<Validator>, mining_flag: Arc<AtomicBool>, message: Message) -> Option<Message> {
+    match message {
+        Message::PeerConnectionRequest { from } => {
+            spawn_peer_connection_task(node.clone(), validator, mining_flag, &from);
+        }
+        Message::PeerConnectionResponse { from: _, known_addresses } => {
+            spawn_connect_to_many_peers(node.clone(), validator, mining_flag, known_addresses);
+        }
+        Message::GetHeaders { from: _, request_id, locator } => {
+            return Some(on_get_headers(node.clone(), request_id, locator).await);
+        }
+        Message::GenesisBlock { from, genesis_block } => {
+            on_genesis_received(node.clone(), validator.db.clone(), from, genesis_block).await;
+        }
+        Message::BlockMined { from, block } => {
+            on_block_received(node.clone(), mining_flag.clone(), validator.clone(), from, block).await;
+        }
+        Message::BlockHashesRequest { from: _, request_id, hashes } => {
+            return Some(on_block_hashes_request(node.clone(), request_id, hashes).await);
+        }
+        Message::NewTransaction { from, transaction } => {
+            on_transaction_received(node.clone(), validator.clone(), from, transaction).await;
+        }
+        Message::GetBlocks { from: _, request_id, hashes } => {
+            let blocks_to_send = get_blocks_with_hash(node.clone(), hashes).await;
+            return Some(Message::BlockList {
+                from: node.lock().await.address.clone(),
+                request_id,
+                blocks: blocks_to_send
+            });
+        }
+        message => {
+            println!("Ignoring unexpected {}", message.get_type());
+        }
     }
+
+    None
 }
 
 async fn send_response(recipient: &mut OwnedWriteHalf, response: Message) {
-    let mut response_bytes = response.to_vec();
-    response_bytes.push(b'\n');
+    let frame = match encode_response(&response) {
+        Ok(frame) => frame,
+        Err(e) => {
+            println!("Failed to encode response: {:?}", e);
+            return;
+        }
+    };
 
-    if let Err(e) = recipient.write_all(&response_bytes).await {
+    if let Err(e) = write_encoded_frame(recipient, &frame).await {
         println!("Failed to send response: {:?}", e);
     }
 }
@@ -99,13 +167,17 @@ pub async fn start_client(node: Arc<Mutex<Node>>, address: String, validator: Ar
     }
 }
 
-pub async fn create_node(args: &Args, validator: Arc<Validator>, mining_flag: Arc<AtomicBool>) -> Arc<Mutex<Node>> {
+pub async fn create_node(args: &Args, blockchain: Blockchain, validator: Arc<Validator>, mining_flag: Arc<AtomicBool>) -> Arc<Mutex<Node>> {
     let (binding_address, peer_address, external_address) = match args.node_type.get_mode() {
         Mode::OPEN { node_address, external_address } => (node_address.clone(), None, external_address),
         Mode::JOIN { node_address, peer_address, external_address } => (node_address.clone(), Some(peer_address.clone()), external_address),
     };
 
-    let node = Arc::new(Mutex::new(Node::new(external_address.clone())));
+    let mut node = Node::new(external_address.clone());
+    node.blockchain = blockchain;
+    node.services = get_services(args);
+    node.mempool = Arc::new(Mutex::new(Mempool::new(args.mempool_max_transactions, args.mempool_expiry)));
+    let node = Arc::new(Mutex::new(node));
     start_peer_connection(node.clone(), validator, mining_flag, binding_address, peer_address).await;
 
     node
@@ -118,9 +190,21 @@ pub async fn start_peer_connection(
     binding_address: String,
     peer_address: Option<String>
 ) {
-    tokio::spawn(start_client(node.clone(), binding_address, validator, mining_flag));
+    tokio::spawn(start_client(node.clone(), binding_address, validator.clone(), mining_flag.clone()));
 
     if peer_address.is_some() {
-        spawn_initial_peer_connection(node.clone(), peer_address.as_ref().unwrap());
+        spawn_initial_peer_connection(node.clone(), validator, mining_flag, peer_address.as_ref().unwrap());
     }
-}
\ No newline at end of file
+}
+
+fn get_services(args: &Args) -> u64 {
+    let mut services = SERVICE_BLOCKS;
+    if matches!(args.node_type, NodeType::FULL(_)) {
+        services |= SERVICE_API;
+    }
+    if !args.no_mining {
+        services |= SERVICE_MINING;
+    }
+
+    services
+}
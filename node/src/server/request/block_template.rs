@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockTemplateRequest {
+    pub miner_address: String,
+    pub transaction_ids: Vec<String>
+}
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MiningSubmissionRequest {
+    pub job_id: String,
+    pub nonce: u64
+}
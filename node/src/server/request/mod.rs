@@ -1 +1,3 @@
+pub mod block_template;
+pub mod mining_submission;
 pub mod transaction;
\ No newline at end of file
@@ -7,5 +7,8 @@ pub struct TransactionRequest {
     pub id: String,
     pub timestamp: i64,
     pub amount: u64,
+    #[serde(default)]
+    pub fee: u64,
+    pub nonce: u64,
     pub signature: String
 }
\ No newline at end of file
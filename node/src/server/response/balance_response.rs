@@ -0,0 +1,16 @@
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct BalanceResponse {
+    address: String,
+    balance: u64
+}
+
+impl BalanceResponse {
+    pub fn new(address: String, balance: u64) -> Self {
+        BalanceResponse {
+            address,
+            balance
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockSubmissionResponse {
+    pub accepted: bool,
+    pub message: String
+}
+
+impl BlockSubmissionResponse {
+    pub fn new(accepted: bool, message: String) -> Self {
+        BlockSubmissionResponse {
+            accepted,
+            message
+        }
+    }
+}
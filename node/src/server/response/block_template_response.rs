@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use crate::chain::block::Block;
+use crate::chain::difficulty::get_target;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockTemplateResponse {
+    pub block: Block,
+    pub target: String,
+    pub fees: u64,
+    pub size: usize
+}
+
+impl BlockTemplateResponse {
+    pub fn new(block: Block) -> Self {
+        BlockTemplateResponse {
+            target: hex::encode(get_target(block.difficulty)),
+            fees: block.get_total_fees().unwrap_or(0),
+            size: block.get_size(),
+            block
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use serde::Serialize;
+use crate::mempool::{Mempool, MempoolEntry};
+
+#[derive(Serialize)]
+pub struct MempoolResponse {
+    count: usize,
+    max_transactions: usize,
+    expiry: i64,
+    transactions: Vec<MempoolEntry>
+}
+
+impl MempoolResponse {
+    pub fn new(mempool: &Mempool) -> Self {
+        let transactions = mempool.get_entries();
+
+        MempoolResponse {
+            count: transactions.len(),
+            max_transactions: mempool.get_max_transactions(),
+            expiry: mempool.get_expiry(),
+            transactions
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PurgeMempoolResponse {
+    purged: usize
+}
+
+impl PurgeMempoolResponse {
+    pub fn new(purged: usize) -> Self {
+        PurgeMempoolResponse {
+            purged
+        }
+    }
+}
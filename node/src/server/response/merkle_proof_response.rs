@@ -0,0 +1,21 @@
+use serde::Serialize;
+use crate::chain::block::{Block, BlockHeader};
+use crate::chain::merkle::MerkleProof;
+
+// The full header is returned so clients can check its proof-of-work before trusting the Merkle root
+#[derive(Serialize)]
+pub struct MerkleProofResponse {
+    block_hash: String,
+    header: BlockHeader,
+    proof: MerkleProof
+}
+
+impl MerkleProofResponse {
+    pub fn new(block: &Block, proof: MerkleProof) -> Self {
+        MerkleProofResponse {
+            block_hash: block.hash.clone(),
+            header: block.get_header(),
+            proof
+        }
+    }
+}
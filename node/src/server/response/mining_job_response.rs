@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use crate::chain::block::Block;
+use crate::chain::difficulty::get_target;
+use crate::chain::transaction::Transaction;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MiningJobResponse {
+    pub job_id: String,
+    pub index: u64,
+    pub timestamp: i64,
+    pub previous_block_hash: String,
+    pub merkle_root: String,
+    pub miner_address: String,
+    pub difficulty: usize,
+    pub target: String,
+    pub transactions: Vec<Transaction>
+}
+
+impl MiningJobResponse {
+    pub fn new(job_id: String, block: &Block) -> Self {
+        MiningJobResponse {
+            job_id,
+            index: block.index,
+            timestamp: block.timestamp,
+            previous_block_hash: block.previous_block_hash.clone(),
+            merkle_root: block.merkle_root.clone(),
+            miner_address: block.miner_address.clone(),
+            difficulty: block.difficulty,
+            target: hex::encode(get_target(block.difficulty)),
+            transactions: block.transactions.clone()
+        }
+    }
+
+    // Block with the job's header fields, enough for a miner to search for a nonce
+    pub fn to_block(&self) -> Block {
+        Block {
+            index: self.index,
+            timestamp: self.timestamp,
+            transactions: self.transactions.clone(),
+            previous_block_hash: self.previous_block_hash.clone(),
+            merkle_root: self.merkle_root.clone(),
+            miner_address: self.miner_address.clone(),
+            nonce: 0,
+            difficulty: self.difficulty,
+            hash: String::new()
+        }
+    }
+}
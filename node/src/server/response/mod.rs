@@ -1,2 +1,10 @@
+pub mod balance_response;
+pub mod block_submission_response;
+pub mod block_template_response;
 pub mod create_user;
+pub mod mempool_response;
+pub mod merkle_proof_response;
+pub mod mining_job_response;
+pub mod nonce_response;
+pub mod supply_response;
 pub mod transaction_response;
\ No newline at end of file
@@ -0,0 +1,16 @@
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct NonceResponse {
+    address: String,
+    nonce: u64
+}
+
+impl NonceResponse {
+    pub fn new(address: String, nonce: u64) -> Self {
+        NonceResponse {
+            address,
+            nonce
+        }
+    }
+}
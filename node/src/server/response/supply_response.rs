@@ -0,0 +1,20 @@
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct SupplyResponse {
+    height: u64,
+    block_subsidy: u64,
+    total_supply: u64,
+    max_supply: Option<u64>
+}
+
+impl SupplyResponse {
+    pub fn new(height: u64, block_subsidy: u64, total_supply: u64, max_supply: Option<u64>) -> Self {
+        SupplyResponse {
+            height,
+            block_subsidy,
+            total_supply,
+            max_supply
+        }
+    }
+}
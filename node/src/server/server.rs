@@ -1,28 +1,59 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use axum::{Json, Router};
-use axum::extract::State;
+use axum::extract::{Path, State};
 use axum::http::StatusCode;
-use axum::response::IntoResponse;
-use axum::routing::post;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
 use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use crate::chain::block::Block;
+use crate::chain::block_validation_type::{BlockValidationType, InvalidBlockReason};
+use crate::chain::emission::{block_subsidy, total_supply};
 use crate::database::validator::Validator;
-use crate::node::Mempool;
+use crate::mining::mining_jobs::MiningJobs;
+use crate::mining::mining_tasks::{create_block_template, create_block_template_from, submit_mined_block};
+use crate::node::Node;
+use crate::server::request::block_template::BlockTemplateRequest;
+use crate::server::request::mining_submission::MiningSubmissionRequest;
 use crate::server::request::transaction::TransactionRequest;
+use crate::chain::merkle::create_proof;
 use crate::chain::transaction::Transaction;
-use crate::chain::wallet::Wallet;
+use crate::server::response::balance_response::BalanceResponse;
+use crate::server::response::block_submission_response::BlockSubmissionResponse;
+use crate::server::response::block_template_response::BlockTemplateResponse;
+use crate::server::response::mempool_response::{MempoolResponse, PurgeMempoolResponse};
+use crate::server::response::merkle_proof_response::MerkleProofResponse;
+use crate::server::response::mining_job_response::MiningJobResponse;
+use crate::server::response::nonce_response::NonceResponse;
+use crate::server::response::supply_response::SupplyResponse;
 use crate::server::response::transaction_response::TransactionResponse;
+use crate::tasks::transaction_tasks::{add_transaction_to_mempool, relay_transaction};
 
 #[derive(Clone)]
 struct ServerState {
-    mempool: Mempool,
-    validator: Arc<Validator>
+    node: Arc<Mutex<Node>>,
+    validator: Arc<Validator>,
+    mining_flag: Arc<AtomicBool>,
+    mining_jobs: Arc<Mutex<MiningJobs>>
 }
 
-pub async fn start_server(mempool: Mempool, validator: Arc<Validator>) -> anyhow::Result<()> {
-    let state = ServerState { mempool, validator };
+pub async fn start_server(node: Arc<Mutex<Node>>, validator: Arc<Validator>, mining_flag: Arc<AtomicBool>) -> anyhow::Result<()> {
+    let state = ServerState { node, validator, mining_flag, mining_jobs: Arc::new(Mutex::new(MiningJobs::default())) };
     let app = Router::new()
         .route("/transaction", post(handle_transaction))
+        .route("/balance/{address}", get(handle_balance))
+        .route("/nonce/{address}", get(handle_nonce))
+        .route("/proof/{transaction_id}", get(handle_proof))
+        .route("/supply/{height}", get(handle_supply))
+        .route("/mempool", get(handle_mempool))
+        .route("/mempool", delete(handle_purge_mempool))
+        .route("/mining/job/{miner_address}", get(handle_mining_job))
+        .route("/mining/submit", post(handle_mining_submission))
+        .route("/block/template/{miner_address}", get(handle_block_template))
+        .route("/block/template", post(handle_selected_block_template))
+        .route("/block/submit", post(handle_block_submission))
         .with_state(state);
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
@@ -38,23 +69,177 @@ async fn handle_transaction(
     State(state): State<ServerState>,
     Json(payload): Json<TransactionRequest>
 ) -> impl IntoResponse {
-    let user_wallet = Wallet::load_from_public_key(payload.sender_public_key.clone());
-
     let transaction = Transaction::load(payload);
-    if !user_wallet.verify_signature(&transaction) {
-        let response = TransactionResponse::new(false, "Invalid signature".to_string());
+    if let Err(e) = add_transaction_to_mempool(state.node.clone(), state.validator.clone(), transaction.clone()).await {
+        let response = TransactionResponse::new(false, e.to_string());
         return (StatusCode::BAD_REQUEST, Json(response))
     }
 
-    if !state.validator.validate_transaction(&transaction).await {
-        let response = TransactionResponse::new(false, "Insufficient funds".to_string());
-        return (StatusCode::BAD_REQUEST, Json(response))
-    }
-
-    state.mempool.lock().await.push(transaction);
-
+    relay_transaction(state.node.clone(), transaction, None).await;
     println!("Transaction added to mempool.");
 
     let response = TransactionResponse::new(true, "Transaction added to mempool".to_string());
     (StatusCode::OK, Json(response))
-}
\ No newline at end of file
+}
+
+async fn handle_balance(
+    State(state): State<ServerState>,
+    Path(address): Path<String>
+) -> impl IntoResponse {
+    let balance = state.node.lock().await.blockchain.state.get_balance(&address);
+
+    (StatusCode::OK, Json(BalanceResponse::new(address, balance)))
+}
+
+async fn handle_nonce(
+    State(state): State<ServerState>,
+    Path(address): Path<String>
+) -> impl IntoResponse {
+    let node = state.node.lock().await;
+    let mempool = node.mempool.lock().await;
+    let nonce = Validator::get_next_nonce(&address, &node.blockchain.state, &mempool);
+
+    (StatusCode::OK, Json(NonceResponse::new(address, nonce)))
+}
+
+async fn handle_supply(
+    State(state): State<ServerState>,
+    Path(height): Path<u64>
+) -> impl IntoResponse {
+    let spec = state.node.lock().await.blockchain.spec.clone();
+    let response = SupplyResponse::new(height, block_subsidy(&spec, height), total_supply(&spec, height), spec.rewards.max_supply);
+
+    (StatusCode::OK, Json(response))
+}
+
+async fn handle_proof(
+    State(state): State<ServerState>,
+    Path(transaction_id): Path<String>
+) -> Response {
+    let node = state.node.lock().await;
+    let proof = node.blockchain.find_transaction_block(&transaction_id)
+        .and_then(|block| create_proof(&block.transactions, &transaction_id).map(|proof| MerkleProofResponse::new(block, proof)));
+
+    match proof {
+        Some(response) => (StatusCode::OK, Json(response)).into_response(),
+        None => {
+            let response = TransactionResponse::new(false, "Transaction not found on the main chain".to_string());
+            (StatusCode::NOT_FOUND, Json(response)).into_response()
+        }
+    }
+}
+
+async fn handle_mempool(State(state): State<ServerState>) -> impl IntoResponse {
+    let mempool = state.node.lock().await.mempool.clone();
+    let response = MempoolResponse::new(&*mempool.lock().await);
+
+    (StatusCode::OK, Json(response))
+}
+
+async fn handle_purge_mempool(State(state): State<ServerState>) -> impl IntoResponse {
+    let mempool = state.node.lock().await.mempool.clone();
+    let purged = mempool.lock().await.purge();
+    println!("Purged {} transactions from mempool", purged);
+
+    (StatusCode::OK, Json(PurgeMempoolResponse::new(purged)))
+}
+
+async fn handle_mining_job(
+    State(state): State<ServerState>,
+    Path(miner_address): Path<String>
+) -> impl IntoResponse {
+    let block = create_block_template(state.node.clone(), miner_address).await;
+    let job_id = state.mining_jobs.lock().await.add(block.clone());
+
+    (StatusCode::OK, Json(MiningJobResponse::new(job_id, &block)))
+}
+
+async fn handle_mining_submission(
+    State(state): State<ServerState>,
+    Json(payload): Json<MiningSubmissionRequest>
+) -> impl IntoResponse {
+    let mut block = match state.mining_jobs.lock().await.get(&payload.job_id) {
+        Some(block) => block.clone(),
+        None => {
+            let response = BlockSubmissionResponse::new(false, "Unknown or stale job".to_string());
+            return (StatusCode::NOT_FOUND, Json(response));
+        }
+    };
+
+    block.nonce = payload.nonce;
+    block.hash = block.create_hash();
+    if !block.has_valid_proof_of_work() {
+        let response = BlockSubmissionResponse::new(false, "Hash does not meet the target".to_string());
+        return (StatusCode::BAD_REQUEST, Json(response));
+    }
+
+    let block_index = block.index;
+    let block_validation_type = submit_mined_block(state.node.clone(), state.validator.db.clone(), block).await;
+
+    submission_response(&state, block_index, block_validation_type)
+}
+
+async fn handle_block_template(
+    State(state): State<ServerState>,
+    Path(miner_address): Path<String>
+) -> impl IntoResponse {
+    let block = create_block_template(state.node.clone(), miner_address).await;
+
+    (StatusCode::OK, Json(BlockTemplateResponse::new(block)))
+}
+
+async fn handle_selected_block_template(
+    State(state): State<ServerState>,
+    Json(payload): Json<BlockTemplateRequest>
+) -> Response {
+    match create_block_template_from(state.node.clone(), payload.miner_address, &payload.transaction_ids).await {
+        Ok(block) => (StatusCode::OK, Json(BlockTemplateResponse::new(block))).into_response(),
+        Err(e) => {
+            let response = BlockSubmissionResponse::new(false, e.to_string());
+            (StatusCode::BAD_REQUEST, Json(response)).into_response()
+        }
+    }
+}
+
+// Solved blocks go through the same validation as locally mined blocks and are then announced.
+// A local submission never starts a sync with peers, so it is refused while the node is syncing.
+async fn handle_block_submission(
+    State(state): State<ServerState>,
+    Json(block): Json<Block>
+) -> impl IntoResponse {
+    if state.node.lock().await.blockchain_locked {
+        let response = BlockSubmissionResponse::new(false, "Node is still syncing the chain".to_string());
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(response));
+    }
+
+    let block_index = block.index;
+    let block_validation_type = submit_mined_block(state.node.clone(), state.validator.db.clone(), block).await;
+
+    submission_response(&state, block_index, block_validation_type)
+}
+
+fn submission_response(state: &ServerState, block_index: u64, block_validation_type: BlockValidationType) -> (StatusCode, Json<BlockSubmissionResponse>) {
+    let status = get_submission_status(&block_validation_type);
+    if status != StatusCode::OK {
+        let response = BlockSubmissionResponse::new(status == StatusCode::ACCEPTED, block_validation_type.to_string());
+        return (status, Json(response));
+    }
+
+    // Local mining restarts on top of the submitted block
+    state.mining_flag.store(false, Ordering::Relaxed);
+    println!("Block {} submitted by external miner", block_index);
+
+    let response = BlockSubmissionResponse::new(true, format!("Block {} accepted", block_index));
+    (status, Json(response))
+}
+
+// Side chain blocks are stored without moving the tip, blocks with an unknown parent are held as orphans
+fn get_submission_status(block_validation_type: &BlockValidationType) -> StatusCode {
+    match block_validation_type {
+        BlockValidationType::Valid | BlockValidationType::Reorg => StatusCode::OK,
+        BlockValidationType::SideChain => StatusCode::ACCEPTED,
+        BlockValidationType::Fork => StatusCode::UNPROCESSABLE_ENTITY,
+        BlockValidationType::Invalid(InvalidBlockReason::KnownBlock) => StatusCode::CONFLICT,
+        BlockValidationType::Invalid(_) => StatusCode::BAD_REQUEST
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use crate::chain::block::Block;
+use crate::chain::block_validation_type::{BlockValidationType, InvalidBlockReason};
+use crate::chain::chain_spec::DifficultySpec;
+use crate::chain::header_chain::HeaderChain;
+use crate::constants::{BLOCK_DOWNLOAD_BATCH_SIZE, MAX_BLOCK_DOWNLOAD_ATTEMPTS, MAX_HEADERS_PER_MESSAGE, SERVICE_BLOCKS};
+use crate::database::operations::DbOperations;
+use crate::network::message::Message;
+use crate::network::message_sender::send_request;
+use crate::node::Node;
+use crate::tasks::persistence_tasks::save_chain_update;
+
+/*
+    Headers-first initial block download.
+    Every peer is asked for its header chain, which is validated without the block bodies,
+    and the chain with the most work is downloaded.
+    Its blocks are requested in batches from all peers serving that chain in parallel
+    over the existing peer connections, and connected in order. Each batch is saved as it connects,
+    so an interrupted sync resumes from the stored chain tip on restart.
+*/
+
+struct PeerHeaders {
+    address: String,
+    header_chain: HeaderChain
+}
+
+struct BlockBatch {
+    number: usize,
+    hashes: Vec<String>,
+    failed_peers: Vec<String>
+}
+
+impl PeerHeaders {
+    fn can_serve(&self, batch: &BlockBatch) -> bool {
+        let last_hash = batch.hashes.last().expect("Block batches are never empty");
+        self.header_chain.get_position(last_hash).is_some() && !batch.failed_peers.contains(&self.address)
+    }
+}
+
+pub async fn sync_chain(node: Arc<Mutex<Node>>, db: DbOperations) -> Result<()> {
+    let (node_address, peer_addresses, local_headers, local_work, spec) = {
+        let locked_node = node.lock().await;
+        (
+            locked_node.address.clone(),
+            // Only peers that advertise serving blocks are asked for them
+            locked_node.peers.values()
+                .filter(|peer| peer.version.services & SERVICE_BLOCKS != 0)
+                .map(|peer| peer.address.clone())
+                .collect::<Vec<String>>(),
+            HeaderChain::from_blocks(&locked_node.blockchain.chain),
+            locked_node.blockchain.get_chain_work(),
+            locked_node.blockchain.spec.difficulty.clone()
+        )
+    };
+
+    let mut peer_headers = vec![];
+    for peer_address in peer_addresses {
+        match download_headers(node.clone(), &node_address, &peer_address, local_headers.clone(), &spec).await {
+            Ok(header_chain) => {
+                println!("Peer {} has a valid header chain of {} blocks", peer_address, header_chain.get_length());
+                peer_headers.push(PeerHeaders { address: peer_address, header_chain });
+            }
+            Err(e) => println!("Failed to download headers from {}: {}", peer_address, e)
+        }
+    }
+
+    let best_headers = peer_headers.iter()
+        .map(|peer| &peer.header_chain)
+        .max_by_key(|header_chain| header_chain.get_chain_work())
+        .ok_or(anyhow!("No peer provided a valid header chain"))?
+        .clone();
+
+    if best_headers.get_chain_work() <= local_work {
+        println!("Chain is up to date at height {}", local_headers.get_length().saturating_sub(1));
+        return Ok(());
+    }
+
+    // Blocks before the fork point are already in the local chain
+    let fork_position = (0..local_headers.get_length())
+        .find(|position| best_headers.hashes.get(*position) != Some(&local_headers.hashes[*position]))
+        .unwrap_or(local_headers.get_length());
+    println!("Downloading blocks {} to {}", fork_position, best_headers.get_length() - 1);
+
+    download_blocks(node, db, &node_address, &peer_headers, &best_headers, fork_position).await
+}
+
+pub async fn on_get_headers(node: Arc<Mutex<Node>>, request_id: u64, locator: Vec<String>) -> Message {
+    let locked_node = node.lock().await;
+    let blockchain = &locked_node.blockchain;
+
+    // The requester's chain matches ours up to the first locator hash on our main chain
+    let start = locator.iter().find_map(|hash| {
+        blockchain.block_tree.get(hash)
+            .map(|block| block.index as usize)
+            .filter(|index| blockchain.chain.get(*index).is_some_and(|block| block.hash == *hash))
+    });
+
+    let headers = match start {
+        Some(index) => blockchain.chain.iter()
+            .skip(index + 1)
+            .take(MAX_HEADERS_PER_MESSAGE)
+            .map(|block| block.get_header())
+            .collect(),
+        None => vec![]
+    };
+
+    Message::Headers {
+        from: locked_node.address.clone(),
+        request_id,
+        headers
+    }
+}
+
+async fn download_headers(
+    node: Arc<Mutex<Node>>,
+    node_address: &str,
+    peer_address: &str,
+    mut header_chain: HeaderChain,
+    spec: &DifficultySpec
+) -> Result<HeaderChain> {
+    loop {
+        let locator = header_chain.get_locator();
+        let response = send_request(node.clone(), peer_address, |request_id| Message::GetHeaders {
+            from: node_address.to_string(),
+            request_id,
+            locator
+        }).await?;
+
+        let headers = match response {
+            Message::Headers { headers, .. } => headers,
+            _ => return Err(anyhow!("Unexpected response to headers request"))
+        };
+
+        let Some(first_header) = headers.first() else {
+            return Ok(header_chain);
+        };
+
+        let previous_length = header_chain.get_length();
+        if !header_chain.truncate_after(&first_header.previous_block_hash) {
+            return Err(anyhow!("Headers do not connect to the local chain"));
+        }
+
+        let received = headers.len();
+        for header in headers {
+            let index = header.index;
+            header_chain.append(header, spec).map_err(|reason| anyhow!("Invalid header {}: {}", index, reason))?;
+        }
+
+        if received < MAX_HEADERS_PER_MESSAGE {
+            return Ok(header_chain);
+        }
+
+        if header_chain.get_length() <= previous_length {
+            return Err(anyhow!("Headers did not extend the chain"));
+        }
+        println!("Downloaded {} headers from {}", header_chain.get_length(), peer_address);
+    }
+}
+
+async fn download_blocks(
+    node: Arc<Mutex<Node>>,
+    db: DbOperations,
+    node_address: &str,
+    peers: &[PeerHeaders],
+    target: &HeaderChain,
+    fork_position: usize
+) -> Result<()> {
+    let mut queue: VecDeque<BlockBatch> = target.hashes[fork_position..]
+        .chunks(BLOCK_DOWNLOAD_BATCH_SIZE)
+        .enumerate()
+        .map(|(number, hashes)| BlockBatch { number, hashes: hashes.to_vec(), failed_peers: vec![] })
+        .collect();
+    let batch_count = queue.len();
+    let target_height = target.get_length() - 1;
+    let mut downloaded: BTreeMap<usize, Vec<Block>> = BTreeMap::new();
+    let mut next_batch = 0;
+
+    while next_batch < batch_count {
+        // Each peer downloads one batch per round
+        let mut requests = JoinSet::new();
+        for peer in peers {
+            let Some(position) = queue.iter().position(|batch| peer.can_serve(batch)) else {
+                continue;
+            };
+
+            let batch = queue.remove(position).expect("Batch position is in the queue");
+            let node = node.clone();
+            let node_address = node_address.to_string();
+            let peer_address = peer.address.clone();
+            requests.spawn(async move {
+                let result = request_blocks(node, &node_address, &peer_address, &batch.hashes).await;
+                (batch, peer_address, result)
+            });
+        }
+
+        if requests.is_empty() {
+            return Err(anyhow!("No peer can serve the remaining blocks"));
+        }
+
+        while let Some(joined) = requests.join_next().await {
+            let (mut batch, peer_address, result) = joined?;
+            match result {
+                Ok(blocks) => {
+                    downloaded.insert(batch.number, blocks);
+                }
+                Err(e) => {
+                    println!("Failed to download blocks from {}: {}", peer_address, e);
+                    batch.failed_peers.push(peer_address);
+                    if batch.failed_peers.len() >= MAX_BLOCK_DOWNLOAD_ATTEMPTS {
+                        return Err(anyhow!("Failed to download blocks after {} attempts", MAX_BLOCK_DOWNLOAD_ATTEMPTS));
+                    }
+                    queue.push_front(batch);
+                }
+            }
+        }
+
+        while let Some(blocks) = downloaded.remove(&next_batch) {
+            let height = connect_blocks(node.clone(), db.clone(), blocks).await?;
+            next_batch += 1;
+            println!("Synced block {} of {} ({:.1}%)", height, target_height, height as f64 * 100.0 / target_height as f64);
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the height of the last connected block
+async fn connect_blocks(node: Arc<Mutex<Node>>, db: DbOperations, blocks: Vec<Block>) -> Result<u64> {
+    let (chain_update, invalid_block) = {
+        let mut locked_node = node.lock().await;
+        let previous_tip_hash = locked_node.blockchain.get_tip_hash();
+        let mut invalid_block = None;
+        for block in &blocks {
+            match locked_node.blockchain.add_block_to_chain(block) {
+                BlockValidationType::Invalid(InvalidBlockReason::KnownBlock) => {}
+                BlockValidationType::Invalid(reason) => {
+                    invalid_block = Some(anyhow!("Downloaded block {} is invalid: {}", block.index, reason));
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        let chain_update = locked_node.blockchain.get_chain_update(&previous_tip_hash);
+        locked_node.reconcile_mempool(&chain_update).await;
+        (chain_update, invalid_block)
+    };
+    save_chain_update(db, &chain_update).await;
+
+    match invalid_block {
+        Some(e) => Err(e),
+        None => Ok(blocks.last().map(|block| block.index).unwrap_or_default())
+    }
+}
+
+// The blocks must be exactly the ones whose headers were validated
+async fn request_blocks(node: Arc<Mutex<Node>>, node_address: &str, peer_address: &str, hashes: &[String]) -> Result<Vec<Block>> {
+    let response = send_request(node, peer_address, |request_id| Message::GetBlocks {
+        from: node_address.to_string(),
+        request_id,
+        hashes: hashes.to_vec()
+    }).await?;
+
+    let blocks = match response {
+        Message::BlockList { blocks, .. } => blocks,
+        _ => return Err(anyhow!("Unexpected response to blocks request"))
+    };
+
+    let matches_headers = blocks.len() == hashes.len() && blocks.iter().zip(hashes).all(|(block, hash)| {
+        block.hash == *hash && block.create_hash() == *hash && block.has_valid_merkle_root()
+    });
+    if !matches_headers {
+        return Err(anyhow!("Blocks do not match the requested headers"));
+    }
+
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_on_get_headers_starts_after_locator() {
+        let node = Arc::new(Mutex::new(Node::new("test_address".to_string())));
+        let genesis = node.lock().await.blockchain.create_genesis_block();
+        let block = Block::new(1, genesis.hash.clone(), vec![], "miner_address".to_string());
+        node.lock().await.blockchain.add_block_without_validation(block.clone()).unwrap();
+
+        let response = on_get_headers(node.clone(), 1, vec!["unknown_hash".to_string(), genesis.hash.clone()]).await;
+        match response {
+            Message::Headers { headers, .. } => assert_eq!(headers, vec![block.get_header()]),
+            _ => panic!("Expected headers response")
+        }
+
+        let response = on_get_headers(node.clone(), 1, vec!["unknown_hash".to_string()]).await;
+        match response {
+            Message::Headers { headers, .. } => assert!(headers.is_empty()),
+            _ => panic!("Expected headers response")
+        }
+    }
+}
@@ -1,151 +1,155 @@
 use std::sync::{atomic, Arc};
 use std::sync::atomic::AtomicBool;
+use anyhow::Result;
 use tokio::sync::Mutex;
 use crate::chain::block::Block;
+use crate::constants::{BLOCK_DOWNLOAD_BATCH_SIZE, MAX_HEADERS_PER_MESSAGE};
+use crate::database::operations::DbOperations;
 use crate::network::message::Message;
-use crate::network::message_sender::{send_message, send_message_expect_response};
+use crate::network::message_sender::send_request;
 use crate::node::Node;
-
-pub async fn wait_and_send_block_hashes(node: Arc<Mutex<Node>>) {
-    // Sleep for 10 seconds to allow time for length responses to be received
-    tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
-
-    let (max_peer_chain_length, blockchain, address) = {
-        let locked_node = node.lock().await;
-        (
-            locked_node.max_peer_chain_length.clone(),
-            locked_node.blockchain.clone(),
-            locked_node.address.clone(),
-        )
-    };
-
-    tokio::spawn(async move {
-        if let Some(max_length) = max_peer_chain_length {
-            let hashes = blockchain.chain
+use crate::tasks::persistence_tasks::save_chain_update;
+
+// Asks the peer where our chains diverge, then downloads the blocks we are missing.
+// Responses hold at most MAX_HEADERS_PER_MESSAGE hashes, so this repeats while they are full.
+pub async fn send_block_hashes_request(node: Arc<Mutex<Node>>, db: DbOperations, mining_flag: Arc<AtomicBool>, peer_address: String) {
+    loop {
+        let (node_address, hashes) = {
+            let locked_node = node.lock().await;
+            let hashes = locked_node.blockchain.chain
                 .iter()
                 .rev()
                 .map(|block| block.hash.clone())
                 .collect();
 
-            let message = Message::BlockHashesRequest {
-                from: address,
-                hashes
-            };
-            if let Some(recipient_node) = node.lock().await.get_peer(max_length.from.clone().as_str()) {
-                send_message(&message, &mut recipient_node.writer).await;
-            } else {
-                println!("No peer found to send block hashes request.");
+            (locked_node.address.clone(), hashes)
+        };
+
+        let response = send_request(node.clone(), &peer_address, |request_id| Message::BlockHashesRequest {
+            from: node_address,
+            request_id,
+            hashes
+        }).await;
+
+        match response {
+            Ok(Message::BlockHashesResponse { hashes, .. }) => {
+                let received = hashes.len();
+                let extended = on_block_hashes_response(node.clone(), db.clone(), mining_flag.clone(), peer_address.clone(), hashes).await;
+                if received < MAX_HEADERS_PER_MESSAGE || !extended {
+                    return;
+                }
+            }
+            Ok(message) => {
+                println!("Unexpected {} in response to block hashes request.", message.get_type());
+                return;
+            }
+            Err(e) => {
+                println!("Block hashes request to {} failed: {}", peer_address, e);
+                return;
             }
-        } else {
-            println!("No peer chain length responses received.");
         }
-    });
+    }
 }
 
-pub async fn on_block_hashes_request(node: Arc<Mutex<Node>>, from: String, hashes: Vec<String>) {
-    let (blockchain, node_address) = {
-        let locked_node = node.lock().await;
-        (locked_node.blockchain.clone(), locked_node.address.clone())
-    };
-
-    for hash in hashes {
-        if let Some(overlap_block) = blockchain.chain.iter().find(|b| b.hash == hash) {
+// Responds with no hashes when none of the requester's blocks are on our chain
+pub async fn on_block_hashes_request(node: Arc<Mutex<Node>>, request_id: u64, hashes: Vec<String>) -> Message {
+    let locked_node = node.lock().await;
+    let chain = &locked_node.blockchain.chain;
 
-            let overlap_index = overlap_block.index as usize;
-            let response_hashes = blockchain.chain[overlap_index..]
-                .iter()
-                .map(|b| b.hash.clone())
-                .collect();
+    let overlap_index = hashes.iter()
+        .find_map(|hash| chain.iter().find(|block| block.hash == *hash))
+        .map(|overlap_block| overlap_block.index as usize);
 
-            let message = Message::BlockHashesResponse {
-                from: node_address.clone(),
-                hashes: response_hashes,
-                common_index: overlap_index
-            };
+    let response_hashes = match overlap_index {
+        Some(index) => chain[index..].iter().take(MAX_HEADERS_PER_MESSAGE).map(|block| block.hash.clone()).collect(),
+        None => vec![]
+    };
 
-            if let Some(peer) = node.lock().await.get_peer(&from) {
-                send_message(&message, &mut peer.writer).await;
-                return;
-            }
-        }
+    Message::BlockHashesResponse {
+        from: locked_node.address.clone(),
+        request_id,
+        hashes: response_hashes,
+        common_index: overlap_index.unwrap_or_default()
     }
 }
 
-pub async fn on_block_hashes_response(node: Arc<Mutex<Node>>, mining_flag: Arc<AtomicBool>, from: String, hashes: Vec<String>, common_index: usize) {
-    let max_peer_chain_length = node.lock().await.max_peer_chain_length.clone();
-    if let Some(expected_peer) = max_peer_chain_length {
-        if expected_peer.from != from {
-            println!("Ignoring block hashes response from unexpected peer: {}", from);
-            return;
-        }
+// Downloads the missing blocks in batches and returns whether they changed our chain
+pub async fn on_block_hashes_response(node: Arc<Mutex<Node>>, db: DbOperations, mining_flag: Arc<AtomicBool>, from: String, hashes: Vec<String>) -> bool {
+    let missing_blocks: Vec<String> = {
+        let locked_node = node.lock().await;
+        let block_tree = &locked_node.blockchain.block_tree;
+        hashes
+            .into_iter()
+            .filter(|hash| !block_tree.contains(hash) && !block_tree.contains_orphan(hash))
+            .collect()
+    };
 
-        let mut blockchain = node.lock().await.blockchain.chain.clone()[..common_index + 1].to_vec();
-        let invalid_blocks = node.lock().await.blockchain.invalid_blocks.clone();
-        let mut missing_blocks: Vec<String> = vec![];
-        let mut valid_blocks: Vec<Block> = vec![];
+    if missing_blocks.is_empty() {
+        println!("No missing blocks to request from peer {}", from);
+        return false;
+    }
 
-        for hash in hashes {
-            if let Some(block) = invalid_blocks.iter().find(|b| b.hash == hash) {
-                valid_blocks.push(block.clone());
-            } else {
-                missing_blocks.push(hash);
+    let mut blocks = Vec::with_capacity(missing_blocks.len());
+    for batch in missing_blocks.chunks(BLOCK_DOWNLOAD_BATCH_SIZE) {
+        let blocks_response = send_get_blocks_request(node.clone(), batch.to_vec(), &from).await;
+        match blocks_response {
+            Ok(Message::BlockList { blocks: batch_blocks, .. }) => blocks.extend(batch_blocks),
+            Ok(_) => {
+                println!("Unexpected message type received in block hashes response.");
+                return false;
+            }
+            Err(e) => {
+                println!("Failed to retrieve missing blocks from peer {}: {}", from, e);
+                return false;
             }
         }
+    }
 
-        let blocks_response = send_get_blocks_request(node.clone(), missing_blocks, &from).await;
-        if let Some(msg) = blocks_response {
-            if let Message::BlockList { blocks, .. } = msg {
-                valid_blocks.extend(blocks);
-                valid_blocks.sort_by_key(|block| block.index);
-
-                let mut last_index = common_index as u64;
-                for block in valid_blocks {
-                    if block.index == last_index + 1 {
-                        blockchain.push(block);
-                        last_index += 1;
-                    }
-                }
-
-                node.lock().await.blockchain.chain = blockchain;
-                node.lock().await.blockchain.invalid_blocks = vec![];
-                println!("Received and added blocks from peer {}", from);
+    // Parents always have a lower index than their children
+    blocks.sort_by_key(|block| block.index);
 
-                mining_flag.store(true, atomic::Ordering::Release);
-            } else {
-                println!("Unexpected message type received in block hashes response.");
-            }
-        } else {
-            println!("Failed to retrieve missing blocks from peer {}", from);
+    let chain_update = {
+        let mut locked_node = node.lock().await;
+        let previous_tip_hash = locked_node.blockchain.get_tip_hash();
+        for block in blocks {
+            locked_node.blockchain.add_block_to_chain(&block);
         }
+
+        let chain_update = locked_node.blockchain.get_chain_update(&previous_tip_hash);
+        locked_node.reconcile_mempool(&chain_update).await;
+        chain_update
+    };
+    println!("Received and added blocks from peer {}", from);
+    save_chain_update(db, &chain_update).await;
+
+    if chain_update.is_empty() {
+        return false;
     }
+
+    // Restart mining on top of the new chain tip
+    mining_flag.store(false, atomic::Ordering::Release);
+    true
 }
 
-pub async fn send_get_blocks_request(node: Arc<Mutex<Node>>, hashes: Vec<String>, recipient: &String) -> Option<Message> {
+pub async fn send_get_blocks_request(node: Arc<Mutex<Node>>, hashes: Vec<String>, recipient: &str) -> Result<Message> {
     let node_address = node.lock().await.address.clone();
 
-    let message = Message::GetBlocks {
+    send_request(node, recipient, |request_id| Message::GetBlocks {
         from: node_address,
+        request_id,
         hashes
-    };
-
-    if let Some(peer) = node.lock().await.get_peer(recipient) {
-        return send_message_expect_response(&message, &mut peer.writer, &mut peer.reader).await;
-    } else {
-        println!("No peer found to send get blocks request.");
-    }
-
-    None
+    }).await
 }
 
 pub async fn get_blocks_with_hash(node: Arc<Mutex<Node>>, hashes: Vec<String>) -> Vec<Block> {
-    let blockchain = node.lock().await.blockchain.clone();
-    let mut blocks_to_send: Vec<Block> = vec![];
-
-    for hash in hashes {
-        if let Some(block) = blockchain.chain.iter().find(|b| b.hash == hash) {
-            blocks_to_send.push(block.clone());
-        }
-    }
-
-    blocks_to_send
+    let locked_node = node.lock().await;
+    let blockchain = &locked_node.blockchain;
+
+    // Only blocks on the main chain are served, at most one download batch per response
+    hashes.iter()
+        .take(BLOCK_DOWNLOAD_BATCH_SIZE)
+        .filter_map(|hash| blockchain.block_tree.get(hash))
+        .filter_map(|block| blockchain.chain.get(block.index as usize).filter(|main_block| main_block.hash == block.hash))
+        .cloned()
+        .collect()
 }
\ No newline at end of file
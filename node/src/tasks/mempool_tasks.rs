@@ -0,0 +1,52 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use tokio::sync::Mutex;
+use crate::constants::MEMPOOL_EXPIRY_CHECK_INTERVAL;
+use crate::mempool::Mempool;
+use crate::node::Node;
+
+pub fn spawn_mempool_expiry_loop(node: Arc<Mutex<Node>>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(MEMPOOL_EXPIRY_CHECK_INTERVAL)).await;
+
+            let mempool = node.lock().await.mempool.clone();
+            let expired_count = mempool.lock().await.remove_expired(Utc::now().timestamp());
+            if expired_count > 0 {
+                println!("Removed {} expired transactions from mempool", expired_count);
+            }
+        }
+    });
+}
+
+pub async fn save_mempool(node: Arc<Mutex<Node>>, path: &Path) {
+    let mempool = node.lock().await.mempool.clone();
+    let locked_mempool = mempool.lock().await;
+
+    match locked_mempool.save_to_file(path) {
+        Ok(_) => println!("Saved {} mempool transactions to {}", locked_mempool.len(), path.display()),
+        Err(e) => println!("Failed to save mempool to {}: {}", path.display(), e)
+    }
+}
+
+// Reloads a saved mempool once the chain is synced so transactions are validated against the latest state
+pub async fn restore_mempool(node: Arc<Mutex<Node>>, path: &Path) {
+    if !path.exists() {
+        return;
+    }
+
+    let entries = match Mempool::load_from_file(path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("Failed to load mempool from {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    let locked_node = node.lock().await;
+    let restored_count = locked_node.mempool.lock().await
+        .restore(entries, &locked_node.blockchain.state, Utc::now().timestamp());
+    println!("Restored {} mempool transactions from {}", restored_count, path.display());
+}
@@ -1,4 +1,6 @@
+pub mod block_sync;
 pub mod fork_handling;
-pub mod new_node_tasks;
 pub mod genesis_tasks;
-pub mod peer_connection;
\ No newline at end of file
+pub mod peer_connection;
+pub mod persistence_tasks;
+pub mod transaction_tasks;pub mod mempool_tasks;
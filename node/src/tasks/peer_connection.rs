@@ -1,17 +1,22 @@
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use tokio::net::tcp::OwnedWriteHalf;
 use tokio::net::TcpStream;
 use tokio::sync::Mutex;
+use crate::database::validator::Validator;
+use crate::network::dispatcher::PendingRequests;
+use crate::network::handshake::{create_version, send_handshake};
 use crate::network::message::Message;
 use crate::network::message_sender::send_message;
+use crate::network::tcp_connection::spawn_peer_reader;
 use crate::node::Node;
 
-pub fn spawn_connect_to_many_peers(node: Arc<Mutex<Node>>, peer_addresses: Vec<String>) {
+pub fn spawn_connect_to_many_peers(node: Arc<Mutex<Node>>, validator: Arc<Validator>, mining_flag: Arc<AtomicBool>, peer_addresses: Vec<String>) {
     tokio::spawn(async move {
         for peer_address in peer_addresses {
             if should_connect_to_peer(node.clone(), &peer_address).await {
                 println!("Connecting to unknown peer: {}", peer_address);
-                connect_to_peer(node.clone(), &peer_address, true).await;
+                connect_to_peer(node.clone(), validator.clone(), mining_flag.clone(), &peer_address, true).await;
             } else {
                 println!("Already connected to peer: {}", peer_address);
             }
@@ -19,21 +24,21 @@ pub fn spawn_connect_to_many_peers(node: Arc<Mutex<Node>>, peer_addresses: Vec<S
     });
 }
 
-pub fn spawn_peer_connection_task(node: Arc<Mutex<Node>>, peer_address: &str) {
+pub fn spawn_peer_connection_task(node: Arc<Mutex<Node>>, validator: Arc<Validator>, mining_flag: Arc<AtomicBool>, peer_address: &str) {
     let address_clone = peer_address.to_string();
     tokio::spawn(async move {
-        connect_to_peer(node.clone(), &address_clone, false).await;
+        connect_to_peer(node.clone(), validator, mining_flag, &address_clone, false).await;
     });
 }
 
-pub fn spawn_initial_peer_connection(node: Arc<Mutex<Node>>, peer_address: &str) {
+pub fn spawn_initial_peer_connection(node: Arc<Mutex<Node>>, validator: Arc<Validator>, mining_flag: Arc<AtomicBool>, peer_address: &str) {
     let address_clone = peer_address.to_string();
     tokio::spawn(async move {
-        connect_to_peer(node.clone(), &address_clone, true).await;
+        connect_to_peer(node.clone(), validator, mining_flag, &address_clone, true).await;
     });
 }
 
-async fn connect_to_peer(node: Arc<Mutex<Node>>, peer_address: &str, initial_connection: bool) {
+async fn connect_to_peer(node: Arc<Mutex<Node>>, validator: Arc<Validator>, mining_flag: Arc<AtomicBool>, peer_address: &str, initial_connection: bool) {
     println!("Received peer connection message from {}", peer_address);
 
     if !should_connect_to_peer(node.clone(), peer_address).await {
@@ -43,8 +48,16 @@ async fn connect_to_peer(node: Arc<Mutex<Node>>, peer_address: &str, initial_con
 
     match TcpStream::connect(peer_address).await {
         Ok(stream) => {
-            println!("Successfully connected to peer {}", peer_address);
-            let (reader, mut writer) = stream.into_split();
+            let (mut reader, mut writer) = stream.into_split();
+            let local_version = create_version(&*node.lock().await);
+            let remote_version = match send_handshake(&local_version, &mut writer, &mut reader).await {
+                Ok(version) => version,
+                Err(e) => {
+                    println!("Handshake with peer {} failed: {}", peer_address, e);
+                    return;
+                }
+            };
+            println!("Successfully connected to peer {} at height {}", peer_address, remote_version.best_height);
 
             if initial_connection {
                 send_peer_connection_request(node.clone(), &mut writer).await;
@@ -53,7 +66,9 @@ async fn connect_to_peer(node: Arc<Mutex<Node>>, peer_address: &str, initial_con
             }
 
             // Save connection
-            node.lock().await.add_peer(peer_address.to_string(), writer, reader);
+            let pending_requests = PendingRequests::default();
+            node.lock().await.add_peer(peer_address.to_string(), writer, pending_requests.clone(), remote_version);
+            spawn_peer_reader(node.clone(), validator, mining_flag, peer_address.to_string(), reader, pending_requests);
         }
         Err(e) => {
             println!("Failed to connect to peer {}: {:?}", peer_address, e);
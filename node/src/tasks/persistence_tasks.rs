@@ -0,0 +1,66 @@
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use crate::chain::block::Block;
+use crate::chain::block_validation_type::BlockValidationType;
+use crate::chain::blockchain::Blockchain;
+use crate::chain::chain_update::ChainUpdate;
+use crate::database::operations::DbOperations;
+use crate::node::Node;
+
+pub async fn restore_blockchain(node: Arc<Mutex<Node>>, db: DbOperations) -> bool {
+    let stored_blocks = match db.get_blocks().await {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            println!("Failed to load stored blocks: {}", e);
+            return false;
+        }
+    };
+
+    if stored_blocks.is_empty() {
+        println!("No stored blocks found.");
+        return false;
+    }
+
+    println!("Validating {} stored blocks...", stored_blocks.len());
+    let mut locked_node = node.lock().await;
+    for block in stored_blocks {
+        if !load_stored_block(&mut locked_node.blockchain, &block) {
+            println!("Stored block {} is invalid... Discarding it and every block after it", block.index);
+            db.delete_blocks_from_index(block.index).await;
+            break;
+        }
+    }
+
+    let chain_length = locked_node.blockchain.get_length();
+    println!("Restored {} blocks from the database", chain_length);
+
+    chain_length > 0
+}
+
+fn load_stored_block(blockchain: &mut Blockchain, block: &Block) -> bool {
+    if blockchain.get_length() == 0 {
+        if !blockchain.is_expected_genesis(block) || block.hash != block.create_hash() || !block.has_valid_merkle_root() {
+            return false;
+        }
+
+        return blockchain.load_starting_block(block.clone());
+    }
+
+    blockchain.add_block_to_chain(block) == BlockValidationType::Valid
+}
+
+pub async fn save_chain_update(db: DbOperations, update: &ChainUpdate) {
+    // Disconnected blocks are ordered from the old tip, so the last one is the lowest
+    if let Some(first_disconnected) = update.disconnected.last() {
+        db.delete_blocks_from_index(first_disconnected.index).await;
+    }
+
+    for block in &update.connected {
+        db.save_block(block).await;
+    }
+
+    // The users table mirrors the balances derived from the chain
+    for (address, balance) in &update.balances {
+        db.set_user_balance(address.clone(), *balance).await;
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use tokio::sync::Mutex;
+use crate::chain::transaction::Transaction;
+use crate::chain::wallet::Wallet;
+use crate::database::validator::Validator;
+use crate::network::message::Message;
+use crate::network::message_sender::broadcast_message_except;
+use crate::node::Node;
+
+// Verifies a transaction from a client or peer and adds it to the mempool
+pub async fn add_transaction_to_mempool(node: Arc<Mutex<Node>>, validator: Arc<Validator>, transaction: Transaction) -> Result<()> {
+    let sender_wallet = Wallet::try_load_from_public_key(&transaction.sender)
+        .ok_or(anyhow!("Invalid sender public key"))?;
+
+    if !sender_wallet.verify_signature(&transaction) {
+        return Err(anyhow!("Invalid signature"));
+    }
+
+    // Only accepted transactions are marked seen, so one rejected for an early nonce can still be relayed later
+    let locked_node = node.lock().await;
+    let mut mempool = locked_node.mempool.lock().await;
+    let transaction_id = transaction.id.clone();
+    validator.validate_transaction(&transaction, &locked_node.blockchain.state, &mempool)?;
+    mempool.add(transaction)?;
+    mempool.mark_seen(&transaction_id);
+
+    Ok(())
+}
+
+// Relays a transaction to every peer other than the one it came from
+pub async fn relay_transaction(node: Arc<Mutex<Node>>, transaction: Transaction, from: Option<&str>) {
+    let node_address = node.lock().await.address.clone();
+    let message = Message::NewTransaction {
+        from: node_address,
+        transaction
+    };
+
+    broadcast_message_except(node, &message, from).await;
+}
+
+pub async fn on_transaction_received(node: Arc<Mutex<Node>>, validator: Arc<Validator>, from: String, transaction: Transaction) {
+    // Each accepted transaction is only processed and relayed once
+    if node.lock().await.mempool.lock().await.has_seen(&transaction.id) {
+        return;
+    }
+
+    match add_transaction_to_mempool(node.clone(), validator, transaction.clone()).await {
+        Ok(_) => {
+            println!("Transaction {} received from {}... Added to mempool", transaction.id, from);
+            relay_transaction(node, transaction, Some(&from)).await;
+        }
+        Err(e) => println!("Transaction {} received from {} rejected: {}", transaction.id, from, e)
+    }
+}
@@ -6,7 +6,7 @@ use MockChain::args::node_type::NodeType;
 use MockChain::chain::block::Block;
 use MockChain::database::connection::Connection;
 use MockChain::database::operations::{DbOperations, MockDatabaseOperations};
-use MockChain::database::structs::recipient_address::RecipientAddress;
+use MockChain::mempool::Mempool;
 use MockChain::node::Node;
 
 static INIT: Once = Once::new();
@@ -38,17 +38,31 @@ pub fn create_node(node_address: String) -> Arc<Mutex<Node>> {
 }
 
 pub fn create_open_node_args(node_address: String) -> Args {
-    let mode = Mode::OPEN { node_address };
+    let mode = Mode::OPEN { external_address: node_address.clone(), node_address };
     let full_node = NodeType::FULL(ModeArgs { mode });
 
-    Args { node_type: full_node }
+    create_args(full_node)
 }
 
 pub fn create_join_node_args(node_address: String, peer_address: String) -> Args {
-    let mode = Mode::JOIN { node_address, peer_address };
+    let mode = Mode::JOIN { external_address: node_address.clone(), node_address, peer_address };
     let full_node = NodeType::FULL(ModeArgs { mode });
 
-    Args { node_type: full_node }
+    create_args(full_node)
+}
+
+fn create_args(node_type: NodeType) -> Args {
+    let mempool = Mempool::default();
+
+    Args {
+        node_type,
+        chain_spec: None,
+        no_mining: false,
+        mining_threads: 1,
+        mempool_max_transactions: mempool.get_max_transactions(),
+        mempool_expiry: mempool.get_expiry(),
+        mempool_file: None
+    }
 }
 
 pub async fn extract_peer_addresses_from_node(node: Arc<Mutex<Node>>) -> Vec<String> {
@@ -65,13 +79,14 @@ pub fn mine_block(block: &mut Block) {
 }
 
 pub fn create_mocked_database() -> Arc<MockDatabaseOperations> {
-    let recipient = RecipientAddress { recipient_address: "recipient".to_string() };
     let mut mocked_db = MockDatabaseOperations::new();
     mocked_db.expect_get_user_balance().returning(|_| Ok(1000));
     mocked_db.expect_update_user_balance().returning(|_, _| true);
-    mocked_db.expect_get_mining_reward_at_block_index().returning(move |_| Ok(recipient.clone()));
-    mocked_db.expect_save_mining_reward().returning(|_| true);
-    mocked_db.expect_create_user_and_update_balance().returning(|_, _| ());
+    mocked_db.expect_save_block().returning(|_| true);
+    mocked_db.expect_set_user_balance().returning(|_, _| true);
+    mocked_db.expect_delete_blocks_from_index().returning(|_| true);
+    mocked_db.expect_get_blocks().returning(|| Ok(vec![]));
+    mocked_db.expect_is_persistent().returning(|| false);
 
     Arc::new(mocked_db)
 }
\ No newline at end of file
@@ -0,0 +1,72 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/*
+    Block header as returned by a node alongside a Merkle proof.
+    The header is hashed with the same fixed layout the node mines,
+    so the wallet can check the proof-of-work itself
+    instead of trusting the Merkle root the node reports.
+*/
+
+#[derive(Deserialize)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub timestamp: i64,
+    pub previous_block_hash: String,
+    pub merkle_root: String,
+    pub miner_address: String,
+    pub nonce: u64,
+    pub difficulty: usize
+}
+
+impl BlockHeader {
+    const SIZE: usize = 8 + 8 + 32 + 32 + 32 + 4 + 8;
+
+    // Big-endian fixed layout: index, timestamp, previous hash, Merkle root, miner address, difficulty, nonce
+    fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.index.to_be_bytes());
+        bytes[8..16].copy_from_slice(&self.timestamp.to_be_bytes());
+        bytes[16..48].copy_from_slice(&to_header_field(&self.previous_block_hash));
+        bytes[48..80].copy_from_slice(&to_header_field(&self.merkle_root));
+        bytes[80..112].copy_from_slice(&to_header_field(&self.miner_address));
+        bytes[112..116].copy_from_slice(&(self.difficulty as u32).to_be_bytes());
+        bytes[116..124].copy_from_slice(&self.nonce.to_be_bytes());
+
+        bytes
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256::digest(self.to_bytes()).into()
+    }
+
+    // The header must hash to the block hash and that hash must have difficulty leading zero bits
+    pub fn has_valid_proof_of_work(&self, block_hash: &str) -> bool {
+        let hash = self.hash();
+
+        hex::encode(hash) == block_hash && leading_zero_bits(&hash) >= self.difficulty
+    }
+}
+
+// Hashes are stored as 32 bytes, any other value (like an address) through its SHA-256
+fn to_header_field(value: &str) -> [u8; 32] {
+    let mut field = [0u8; 32];
+    match hex::decode(value) {
+        Ok(bytes) if bytes.len() == 32 => field.copy_from_slice(&bytes),
+        _ => field = Sha256::digest(value.as_bytes()).into()
+    }
+
+    field
+}
+
+fn leading_zero_bits(hash: &[u8; 32]) -> usize {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros() as usize;
+        if *byte != 0 {
+            break;
+        }
+    }
+
+    bits
+}
@@ -1,18 +1,28 @@
 use eframe::egui;
+use serde::Deserialize;
 use crate::transaction::Transaction;
 use crate::wallet::Wallet;
 
 pub struct TransactionForm {
     receiver_address: String,
     amount: String,
+    fee: String,
+    error: Option<String>,
     pub endpoint: String
 }
 
+#[derive(Deserialize)]
+struct NonceResponse {
+    nonce: u64
+}
+
 impl Default for TransactionForm {
     fn default() -> Self {
         Self {
             receiver_address: "".to_string(),
             amount: "".to_string(),
+            fee: "0".to_string(),
+            error: None,
             endpoint: "http://localhost:3000".to_string()
         }
     }
@@ -34,13 +44,30 @@ impl TransactionForm {
             ui.add_space(1.0);
             ui.text_edit_singleline(&mut self.amount);
 
+            ui.add_space(3.0);
+            ui.label("Fee (optional, paid to the miner)");
+            ui.add_space(1.0);
+            ui.text_edit_singleline(&mut self.fee);
+
             ui.add_space(3.0);
             if ui.button("Create").clicked() {
                 let amount: u64 = self.amount.clone().parse().unwrap();
-                let mut new_tx = Transaction::new(wallet.get_public_key(), self.receiver_address.clone(), amount);
-                wallet.create_signature(&mut new_tx);
+                let fee: u64 = self.fee.trim().parse().unwrap_or(0);
+                match self.fetch_next_nonce(&wallet.address) {
+                    Ok(nonce) => {
+                        let mut new_tx = Transaction::new(wallet.get_public_key(), self.receiver_address.clone(), amount, fee, nonce);
+                        wallet.create_signature(&mut new_tx);
 
-                *current_transaction = Some(new_tx);
+                        *current_transaction = Some(new_tx);
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(format!("Failed to fetch nonce: {}", e))
+                }
+            }
+
+            if let Some(error) = &self.error {
+                ui.add_space(3.0);
+                ui.label(error);
             }
 
             ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
@@ -51,4 +78,12 @@ impl TransactionForm {
             });
         });
     }
-}
\ No newline at end of file
+
+    // The node counts both mined and pending transactions when returning the next nonce
+    fn fetch_next_nonce(&self, address: &str) -> reqwest::Result<u64> {
+        let endpoint = format!("{}/nonce/{}", self.endpoint, address);
+        let response: NonceResponse = reqwest::blocking::get(endpoint)?.json()?;
+
+        Ok(response.nonce)
+    }
+}
@@ -1,16 +1,19 @@
 use eframe::egui;
 use reqwest::blocking::Response;
 use serde_json::json;
+use crate::merkle::{verify_proof, MerkleProofResponse};
 use crate::transaction::Transaction;
 
 pub struct TransactionPanel {
-    transaction_response: Option<String>
+    transaction_response: Option<String>,
+    confirmation: Option<String>
 }
 
 impl Default for TransactionPanel {
     fn default() -> Self {
         Self {
-            transaction_response: None
+            transaction_response: None,
+            confirmation: None
         }
     }
 }
@@ -26,6 +29,8 @@ impl TransactionPanel {
                     "id": tx.id,
                     "timestamp": tx.timestamp,
                     "amount": tx.amount,
+                    "fee": tx.fee,
+                    "nonce": tx.nonce,
                     "signature": signature
                 });
 
@@ -56,6 +61,16 @@ impl TransactionPanel {
                         ui.add_space(5.0);
                         ui.label(self.transaction_response.clone().unwrap());
                     }
+                    if let Some(tx) = current_transaction {
+                        ui.add_space(5.0);
+                        if ui.button("Check confirmation").clicked() {
+                            self.confirmation = Some(Self::check_confirmation(tx, request_endpoint));
+                        }
+                        if let Some(confirmation) = &self.confirmation {
+                            ui.add_space(5.0);
+                            ui.label(confirmation);
+                        }
+                    }
                 });
             });
     }
@@ -75,6 +90,32 @@ impl TransactionPanel {
         self.transaction_response = Some(body);
     }
 
+    // Confirms the transaction is in a block using only its Merkle proof.
+    // The Merkle root is only trusted once the header it comes from carries valid proof-of-work.
+    fn check_confirmation(transaction: &Transaction, request_endpoint: &str) -> String {
+        let endpoint = format!("{}/proof/{}", request_endpoint, transaction.id);
+        let response = reqwest::blocking::get(endpoint)
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.json::<MerkleProofResponse>());
+
+        match response {
+            Ok(proof_response) => {
+                let header = &proof_response.header;
+                if !header.has_valid_proof_of_work(&proof_response.block_hash) {
+                    return "Block header has invalid proof-of-work".to_string();
+                }
+
+                let leaf_matches = proof_response.proof.leaf == hex::encode(transaction.signed_hash());
+                if leaf_matches && verify_proof(&proof_response.proof, &header.merkle_root) {
+                    format!("Transaction confirmed in block {}", header.index)
+                } else {
+                    "Invalid Merkle proof received".to_string()
+                }
+            }
+            Err(_) => "Transaction not confirmed yet".to_string()
+        }
+    }
+
     fn get_body_from_response(response: reqwest::Result<Response>) -> String {
         if let Ok(resp) = response {
             return resp.text().unwrap_or_else(|err| "Error reading response body: ".to_string() + &err.to_string())
@@ -2,6 +2,8 @@ use crate::gui::gui::Gui;
 
 mod wallet;
 mod transaction;
+mod merkle;
+mod block_header;
 mod gui;
 
 fn main() -> Result<(), eframe::Error> {
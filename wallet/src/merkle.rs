@@ -0,0 +1,68 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use crate::block_header::BlockHeader;
+
+// Must match the node's Merkle tree hashing
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+#[derive(Deserialize)]
+pub struct ProofStep {
+    pub hash: String,
+    pub is_left: bool
+}
+
+#[derive(Deserialize)]
+pub struct MerkleProof {
+    pub leaf: String,
+    pub steps: Vec<ProofStep>
+}
+
+#[derive(Deserialize)]
+pub struct MerkleProofResponse {
+    pub block_hash: String,
+    pub header: BlockHeader,
+    pub proof: MerkleProof
+}
+
+// Recomputes the Merkle root from the leaf and its siblings
+pub fn verify_proof(proof: &MerkleProof, merkle_root: &str) -> bool {
+    let mut current = match decode_hash(&proof.leaf) {
+        Some(hash) => hash_leaf(&hash),
+        None => return false
+    };
+
+    for step in &proof.steps {
+        let sibling = match decode_hash(&step.hash) {
+            Some(hash) => hash,
+            None => return false
+        };
+
+        current = if step.is_left {
+            hash_pair(&sibling, &current)
+        } else {
+            hash_pair(&current, &sibling)
+        };
+    }
+
+    hex::encode(current) == merkle_root
+}
+
+fn hash_leaf(leaf: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(leaf);
+    hasher.finalize().into()
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn decode_hash(hash: &str) -> Option<[u8; 32]> {
+    hex::decode(hash).ok()?.try_into().ok()
+}
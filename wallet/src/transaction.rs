@@ -1,47 +1,68 @@
 use chrono::Utc;
 use secp256k1::ecdsa::Signature;
 use serde::{Deserialize, Serialize};
-use serde_json::to_string;
 use sha2::{Digest, Sha256};
 use hex::encode;
 
+#[derive(Serialize)]
+struct UnsignedTransaction<'a> {
+    sender: &'a str,
+    recipient: &'a str,
+    amount: u64,
+    fee: u64,
+    timestamp: i64,
+    nonce: u64
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Transaction {
     pub sender: String,
     pub recipient: String,
     pub amount: u64,
+    #[serde(default)]
+    pub fee: u64,
     pub timestamp: i64,
-
-    #[serde(skip_serializing, skip_deserializing)]
+    pub nonce: u64,
     pub id: String,
-    #[serde(skip_serializing, skip_deserializing)]
     pub signature: Option<Signature>
 }
 
 impl Transaction {
-    pub fn new(sender_public_key: String, recipient_address: String, amount: u64) -> Self {
+    pub fn new(sender_public_key: String, recipient_address: String, amount: u64, fee: u64, nonce: u64) -> Self {
         let mut transaction = Self {
             sender: sender_public_key,
             recipient: recipient_address,
             amount,
+            fee,
             timestamp: Utc::now().timestamp(),
+            nonce,
             id: "".to_string(),
             signature: None
         };
 
-        transaction.id = transaction.create_hash();
+        transaction.id = encode(transaction.hash());
 
         transaction
     }
 
-    fn create_hash(&self) -> String {
-        let serialized_tx = to_string(self).expect("Failed to serialize transaction");
+    // Bytes covered by the signature, must match the node's serialization
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let unsigned_transaction = UnsignedTransaction {
+            sender: &self.sender,
+            recipient: &self.recipient,
+            amount: self.amount,
+            fee: self.fee,
+            timestamp: self.timestamp,
+            nonce: self.nonce
+        };
 
-        Self::calculate_hash(serialized_tx)
+        serde_json::to_vec(&unsigned_transaction).expect("Failed to serialize transaction")
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        serde_json::to_vec(self).expect("Failed to serialize transaction")
+    // Hash of the full signed transaction, the leaf used in the node's Merkle tree
+    pub fn signed_hash(&self) -> [u8; 32] {
+        let serialized = serde_json::to_vec(self).expect("Failed to serialize transaction");
+        Sha256::digest(serialized).into()
     }
 
     pub fn hash(&self) -> [u8; 32] {
@@ -49,12 +70,4 @@ impl Transaction {
         hasher.update(self.to_bytes());
         hasher.finalize().into()
     }
-
-    fn calculate_hash(serialized_data: String) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(serialized_data.as_bytes());
-        let result = hasher.finalize();
-
-        encode(result)
-    }
-}
\ No newline at end of file
+}